@@ -0,0 +1,275 @@
+use std::{
+    cell::Cell,
+    fmt::{self, Display, Formatter},
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+};
+
+use lewton::inside_ogg::OggStreamReader;
+
+/// File names the game looks for next to a `level.json`, in order of preference.
+pub const SONG_FILE_NAMES: [&str; 2] = ["song.ogg", "song.wav"];
+
+/// A fully decoded song.
+#[derive(Clone)]
+pub struct Song {
+    pub path: PathBuf,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Interleaved samples from -1 to 1.
+    pub samples: Arc<[f32]>,
+}
+
+impl Song {
+    /// Decodes an OGG Vorbis or WAV file.
+    pub fn load(path: &Path) -> Result<Self, AudioError> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let (sample_rate, channels, samples) = match extension.as_deref() {
+            Some("wav") => {
+                let reader = hound::WavReader::open(path)?;
+                let spec = reader.spec();
+                let samples = match spec.sample_format {
+                    hound::SampleFormat::Float => {
+                        reader.into_samples::<f32>().collect::<Result<Vec<_>, _>>()
+                    }
+                    hound::SampleFormat::Int => {
+                        let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+                        reader
+                            .into_samples::<i32>()
+                            .map(|sample| sample.map(|sample| sample as f32 / scale))
+                            .collect()
+                    }
+                }?;
+                (spec.sample_rate, spec.channels, samples)
+            }
+            Some("ogg") => {
+                let mut reader = OggStreamReader::new(File::open(path)?)?;
+                let mut samples = Vec::new();
+                while let Some(packet) = reader.read_dec_packet_itl()? {
+                    samples.extend(packet.into_iter().map(|sample| f32::from(sample) / 32768.0));
+                }
+                (
+                    reader.ident_hdr.audio_sample_rate,
+                    u16::from(reader.ident_hdr.audio_channels),
+                    samples,
+                )
+            }
+            _ => return Err(AudioError::Unsupported),
+        };
+
+        Ok(Self {
+            path: path.to_owned(),
+            sample_rate,
+            channels,
+            samples: samples.into(),
+        })
+    }
+
+    /// Finds the song stored beside a level file, if there is one.
+    pub fn find_beside(level: &Path) -> Option<PathBuf> {
+        let directory = level.parent()?;
+        SONG_FILE_NAMES
+            .into_iter()
+            .map(|name| directory.join(name))
+            .find(|path| path.is_file())
+    }
+
+    /// Length in seconds.
+    pub fn duration(&self) -> f32 {
+        self.samples.len() as f32 / f32::from(self.channels) / self.sample_rate as f32
+    }
+
+    /// Index of the first interleaved sample at `position` seconds in.
+    #[cfg(any(feature = "rodio", test))]
+    pub fn sample_index(&self, position: f32) -> usize {
+        let frame = (position.max(0.0) * self.sample_rate as f32) as usize;
+        (frame * usize::from(self.channels)).min(self.samples.len())
+    }
+}
+
+#[derive(Debug)]
+pub enum AudioError {
+    Io(io::Error),
+    Wav(hound::Error),
+    Ogg(lewton::VorbisError),
+    Unsupported,
+}
+
+impl Display for AudioError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Wav(err) => write!(f, "invalid WAV file: {err}"),
+            Self::Ogg(err) => write!(f, "invalid OGG file: {err}"),
+            Self::Unsupported => write!(f, "only OGG and WAV files are supported"),
+        }
+    }
+}
+
+impl From<io::Error> for AudioError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<hound::Error> for AudioError {
+    fn from(err: hound::Error) -> Self {
+        Self::Wav(err)
+    }
+}
+
+impl From<lewton::VorbisError> for AudioError {
+    fn from(err: lewton::VorbisError) -> Self {
+        Self::Ogg(err)
+    }
+}
+
+/// Somewhere to play songs.
+pub trait Backend {
+    /// Starts playing `song` from `position` seconds in, replacing anything already playing.
+    fn play(&mut self, song: &Song, position: f32);
+    fn stop(&mut self);
+}
+
+/// Chooses the system's audio output, falling back to silence if there isn't one.
+pub fn default_backend() -> Box<dyn Backend> {
+    #[cfg(feature = "rodio")]
+    if let Some(backend) = RodioBackend::new() {
+        return Box::new(backend);
+    }
+    Box::<NullBackend>::default()
+}
+
+/// Plays nothing, for machines without audio output.
+#[derive(Default)]
+pub struct NullBackend {
+    /// Where in the song playback would be starting from, if anywhere.
+    pub playing: Rc<Cell<Option<f32>>>,
+}
+
+impl Backend for NullBackend {
+    fn play(&mut self, _: &Song, position: f32) {
+        self.playing.set(Some(position));
+    }
+
+    fn stop(&mut self) {
+        self.playing.set(None);
+    }
+}
+
+#[cfg(feature = "rodio")]
+pub struct RodioBackend {
+    // Playback stops when the stream is dropped
+    _stream: rodio::OutputStream,
+    handle: rodio::OutputStreamHandle,
+    sink: Option<rodio::Sink>,
+}
+
+#[cfg(feature = "rodio")]
+impl RodioBackend {
+    pub fn new() -> Option<Self> {
+        let (stream, handle) = rodio::OutputStream::try_default().ok()?;
+        Some(Self {
+            _stream: stream,
+            handle,
+            sink: None,
+        })
+    }
+}
+
+#[cfg(feature = "rodio")]
+impl Backend for RodioBackend {
+    fn play(&mut self, song: &Song, position: f32) {
+        self.stop();
+        if let Ok(sink) = rodio::Sink::try_new(&self.handle) {
+            sink.append(SongSource {
+                index: song.sample_index(position),
+                song: song.clone(),
+            });
+            self.sink = Some(sink);
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(sink) = self.sink.take() {
+            sink.stop();
+        }
+    }
+}
+
+/// Streams a song's samples without copying them.
+#[cfg(feature = "rodio")]
+struct SongSource {
+    song: Song,
+    index: usize,
+}
+
+#[cfg(feature = "rodio")]
+impl Iterator for SongSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.song.samples.get(self.index).copied();
+        self.index += 1;
+        sample
+    }
+}
+
+#[cfg(feature = "rodio")]
+impl rodio::Source for SongSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.song.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.song.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+/// Writes a mono sine wave to a WAV file for tests.
+#[cfg(test)]
+pub fn write_sine_wav(path: &Path, frequency: f32, seconds: f32) {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).unwrap();
+    for i in 0..(seconds * spec.sample_rate as f32) as u32 {
+        let time = i as f32 / spec.sample_rate as f32;
+        let sample = (time * frequency * std::f32::consts::TAU).sin();
+        writer
+            .write_sample((sample * 0.5 * f32::from(i16::MAX)) as i16)
+            .unwrap();
+    }
+    writer.finalize().unwrap();
+}
+
+#[cfg(test)]
+#[test]
+fn load_wav() {
+    let path = std::env::temp_dir().join(format!("manifest-sine-{}.wav", std::process::id()));
+    write_sine_wav(&path, 440.0, 2.0);
+    let song = Song::load(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(song.sample_rate, 8000);
+    assert_eq!(song.channels, 1);
+    assert!((song.duration() - 2.0).abs() < 0.001);
+    assert!(song.samples.iter().all(|sample| sample.abs() <= 0.5));
+    assert_eq!(song.sample_index(1.0), 8000);
+}
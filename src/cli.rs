@@ -0,0 +1,341 @@
+use std::path::Path;
+
+use crate::{
+    project::Project,
+    render::{Animation, Image, DEFAULT_SIZE, MAX_SIZE},
+    validate::{validate, Severity},
+};
+
+const USAGE: &str = "\
+Usage:
+    manifest [level.json]                     Open the editor
+    manifest validate <level.json>...         Check levels for problems
+    manifest fmt [--check] <level.json>...    Rewrite levels in the editor's formatting
+    manifest info <level.json>                Summarise a level
+    manifest render <level.json> --out <image.png> [--size <pixels>]
+                    [--beat <beat> | --step <shape>:<step>]
+                                              Draw a level to an image, as it is
+                                              at a beat or step if given
+    manifest render <level.json> --out <animation.gif> [--size <pixels>]
+                                              Animate a level at its tempo";
+
+/// Runs a subcommand if `args` starts with one, returning the exit code.
+///
+/// Returns `None` if the editor should open instead.
+pub fn run(args: &[String]) -> Option<i32> {
+    let (command, args) = args.split_first()?;
+    let result = match command.as_str() {
+        "validate" => validate_levels(args),
+        "fmt" => format_levels(args),
+        "info" => info(args),
+        "render" => render(args),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(0)
+        }
+        _ => return None,
+    };
+    Some(result.unwrap_or_else(|err| {
+        eprintln!("manifest {command}: {err}");
+        if let Error::Usage(_) = err {
+            eprintln!("\n{USAGE}");
+        }
+        2
+    }))
+}
+
+enum Error {
+    Usage(String),
+    Failed(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Usage(message) | Self::Failed(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+fn load(path: &str) -> Result<Project, Error> {
+    Project::load(Path::new(path)).map_err(|err| Error::Failed(format!("{path}: {err}")))
+}
+
+fn levels(args: &[String]) -> Result<&[String], Error> {
+    if args.is_empty() {
+        return Err(Error::Usage("no level files given".to_owned()));
+    }
+    if let Some(flag) = args.iter().find(|arg| arg.starts_with("--")) {
+        return Err(Error::Usage(format!("unknown option {flag}")));
+    }
+    Ok(args)
+}
+
+/// Prints every problem in each level, failing if any have errors.
+fn validate_levels(args: &[String]) -> Result<i32, Error> {
+    let mut code = 0;
+    for path in levels(args)? {
+        let project = match load(path) {
+            Ok(project) => project,
+            Err(err) => {
+                eprintln!("{err}");
+                code = 1;
+                continue;
+            }
+        };
+        for diagnostic in validate(&project) {
+            println!("{path}: {diagnostic}");
+            if diagnostic.severity == Severity::Error {
+                code = 1;
+            }
+        }
+    }
+    Ok(code)
+}
+
+/// Rewrites levels as the editor would save them, or with `--check` only
+/// reports the ones that would change.
+fn format_levels(args: &[String]) -> Result<i32, Error> {
+    let check = args.first().is_some_and(|arg| arg == "--check");
+    let mut code = 0;
+    for path in levels(&args[usize::from(check)..])? {
+        match format_level(path, check) {
+            Ok(unformatted) => {
+                if unformatted && check {
+                    code = 1;
+                }
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                code = 1;
+            }
+        }
+    }
+    Ok(code)
+}
+
+/// Formats one level, returning whether it wasn't formatted already.
+fn format_level(path: &str, check: bool) -> Result<bool, Error> {
+    let project = load(path)?;
+    let formatted = json::stringify_pretty(project.as_json(), 4);
+    let original =
+        std::fs::read_to_string(path).map_err(|err| Error::Failed(format!("{path}: {err}")))?;
+    if formatted == original {
+        return Ok(false);
+    }
+    if check {
+        println!("{path} is not formatted");
+    } else {
+        project
+            .save(Path::new(path))
+            .map_err(|err| Error::Failed(format!("{path}: {err}")))?;
+        println!("Formatted {path}");
+    }
+    Ok(true)
+}
+
+fn info(args: &[String]) -> Result<i32, Error> {
+    let [path] = levels(args)? else {
+        return Err(Error::Usage("expected one level file".to_owned()));
+    };
+    let project = load(path)?;
+    let header = &project.header;
+    println!("Name:           {}", header.name);
+    println!("Genre:          {}", header.genre);
+    println!("Level author:   {}", header.level_author);
+    println!("Song author:    {}", header.song_author);
+    println!("BPM:            {}", header.bpm);
+    println!(
+        "Time signature: {}/{}",
+        header.time_signature_top, header.time_signature_bottom
+    );
+    println!(
+        "Offset:         {}{}",
+        header.effective_offset(),
+        if header.manual_offset {
+            " (manual)"
+        } else {
+            ""
+        }
+    );
+    println!(
+        "Background:     color {}, {}",
+        header.bg_color, header.background_effect
+    );
+    println!("Shapes:         {}", project.shapes.len());
+    let duration = project.duration();
+    println!(
+        "Duration:       {duration} beats ({:.1}s)",
+        duration as f32 * header.beat_length()
+    );
+    Ok(0)
+}
+
+fn render(args: &[String]) -> Result<i32, Error> {
+    let mut level = None;
+    let mut out = None;
+    let mut size = DEFAULT_SIZE;
+    let mut beat = None;
+    let mut step = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| Error::Usage(format!("{name} needs a value")))
+        };
+        match arg.as_str() {
+            "--out" | "-o" => out = Some(value(arg)?),
+            "--size" => {
+                let value = value(arg)?;
+                size = value
+                    .parse()
+                    .ok()
+                    .filter(|size| (1..=MAX_SIZE).contains(size))
+                    .ok_or_else(|| {
+                        Error::Usage(format!("invalid size {value}, expected 1 to {MAX_SIZE}"))
+                    })?;
+            }
+            "--beat" => {
+                let value = value(arg)?;
+                beat = Some(
+                    value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|beat| *beat >= 0.0)
+                        .ok_or_else(|| Error::Usage(format!("invalid beat {value}")))?,
+                );
+            }
+            "--step" => {
+                let value = value(arg)?;
+                // Numbered from 1, as in the editor
+                let parsed = value.split_once(':').and_then(|(shape, step)| {
+                    let shape = shape.parse::<usize>().ok()?.checked_sub(1)?;
+                    let step = step.parse::<usize>().ok()?.checked_sub(1)?;
+                    Some((shape, step))
+                });
+                step = Some(parsed.ok_or_else(|| {
+                    Error::Usage(format!("invalid step {value}, expected <shape>:<step>"))
+                })?);
+            }
+            flag if flag.starts_with("--") => {
+                return Err(Error::Usage(format!("unknown option {flag}")))
+            }
+            _ if level.is_none() => level = Some(arg),
+            _ => return Err(Error::Usage(format!("unexpected argument {arg}"))),
+        }
+    }
+    let level = level.ok_or_else(|| Error::Usage("no level file given".to_owned()))?;
+    let out = out.ok_or_else(|| Error::Usage("no output file given with --out".to_owned()))?;
+    let animate = Path::new(out)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("gif"));
+    if beat.is_some() && step.is_some() {
+        return Err(Error::Usage(
+            "--beat and --step can't be used together".to_owned(),
+        ));
+    }
+    if animate && (beat.is_some() || step.is_some()) {
+        return Err(Error::Usage(
+            "animations always show the whole level".to_owned(),
+        ));
+    }
+
+    let project = load(level)?;
+    if let Some((shape, step)) = step {
+        beat = Some(
+            project
+                .step_beat(shape, step)
+                .ok_or_else(|| Error::Failed(format!("{level} has no shape {}", shape + 1)))?,
+        );
+    }
+    let saved = if animate {
+        Animation::new(&project, size)
+            .save_gif(Path::new(out))
+            .map_err(|err| err.to_string())
+    } else {
+        match beat {
+            Some(beat) => Image::at_beat(&project, beat, size),
+            None => Image::render(&project, usize::MAX, size),
+        }
+        .save_png(Path::new(out))
+        .map_err(|err| err.to_string())
+    };
+    saved.map_err(|err| Error::Failed(format!("{out}: {err}")))?;
+    Ok(0)
+}
+
+#[cfg(test)]
+#[test]
+fn exit_codes() {
+    use crate::shape::Shape;
+
+    let directory = std::env::temp_dir().join(format!("manifest-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = |name: &str| directory.join(name).display().to_string();
+    let run = |args: &[&str]| run(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+
+    let valid = Project {
+        shapes: vec![Shape::default()],
+        ..Default::default()
+    };
+    valid.save(Path::new(&path("valid.json"))).unwrap();
+    Project::default()
+        .save(Path::new(&path("empty.json")))
+        .unwrap();
+    std::fs::write(path("messy.json"), json::stringify(valid.as_json())).unwrap();
+
+    assert_eq!(run(&["level.json"]), None);
+    assert_eq!(run(&["validate", &path("valid.json")]), Some(0));
+    assert_eq!(
+        run(&["validate", &path("valid.json"), &path("empty.json")]),
+        Some(1)
+    );
+    assert_eq!(run(&["validate", &path("missing.json")]), Some(1));
+    assert_eq!(run(&["fmt", "--check", &path("valid.json")]), Some(0));
+    assert_eq!(run(&["fmt", "--check", &path("messy.json")]), Some(1));
+    assert_eq!(run(&["fmt", &path("messy.json")]), Some(0));
+    assert_eq!(run(&["fmt", "--check", &path("messy.json")]), Some(0));
+    assert_eq!(run(&["info", &path("valid.json")]), Some(0));
+    assert_eq!(run(&["render", &path("valid.json")]), Some(2));
+    assert_eq!(
+        run(&["render", &path("valid.json"), "--out", &path("valid.png")]),
+        Some(0)
+    );
+    assert!(directory.join("valid.png").is_file());
+    assert_eq!(
+        run(&[
+            "render",
+            &path("valid.json"),
+            "-o",
+            &path("valid.png"),
+            "--step",
+            "1:1"
+        ]),
+        Some(0)
+    );
+    assert_eq!(
+        run(&[
+            "render",
+            &path("valid.json"),
+            "-o",
+            &path("valid.png"),
+            "--step",
+            "2:1"
+        ]),
+        Some(2)
+    );
+    assert_eq!(
+        run(&[
+            "render",
+            &path("valid.json"),
+            "-o",
+            &path("valid.gif"),
+            "--size",
+            "60"
+        ]),
+        Some(0)
+    );
+    assert!(directory.join("valid.gif").is_file());
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
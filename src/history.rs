@@ -0,0 +1,83 @@
+use std::mem::replace;
+
+use crate::project::Project;
+
+/// Undo and redo stacks of whole-project snapshots.
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Project>,
+    redo: Vec<Project>,
+    /// The project as of the last recorded step.
+    current: Project,
+}
+
+impl History {
+    pub fn new(project: &Project) -> Self {
+        Self {
+            current: project.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Records `project` as a new step if it changed since the last one.
+    ///
+    /// While `continuing` is set (e.g. a drag is still in progress) nothing is
+    /// recorded, so the whole edit becomes a single step once it finishes.
+    pub fn record(&mut self, project: &Project, continuing: bool) {
+        if continuing || *project == self.current {
+            return;
+        }
+        self.undo.push(replace(&mut self.current, project.clone()));
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self, project: &mut Project) {
+        self.record(project, false);
+        if let Some(previous) = self.undo.pop() {
+            self.redo.push(replace(&mut self.current, previous));
+            *project = self.current.clone();
+        }
+    }
+
+    pub fn redo(&mut self, project: &mut Project) {
+        self.record(project, false);
+        if let Some(next) = self.redo.pop() {
+            self.undo.push(replace(&mut self.current, next));
+            *project = self.current.clone();
+        }
+    }
+
+    pub fn can_undo(&self, project: &Project) -> bool {
+        !self.undo.is_empty() || *project != self.current
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn undo_redo() {
+    let mut project = Project::default();
+    let mut history = History::new(&project);
+
+    project.header.name = "First".to_owned();
+    history.record(&project, false);
+    project.header.name = "Dragging".to_owned();
+    history.record(&project, true);
+    project.header.name = "Second".to_owned();
+    history.record(&project, true);
+    history.record(&project, false);
+
+    history.undo(&mut project);
+    assert_eq!(project.header.name, "First");
+    history.undo(&mut project);
+    assert_eq!(project.header.name, Project::default().header.name);
+    assert!(!history.can_undo(&project));
+
+    history.redo(&mut project);
+    history.redo(&mut project);
+    assert_eq!(project.header.name, "Second");
+    assert!(!history.can_redo());
+}
@@ -0,0 +1,311 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use egui::{Event, InputState, Key, Modifiers};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+macro_rules! actions {
+    ($($action: ident, $id: literal, $name: literal, $modifiers: ident + $key: ident;)*) => {
+        /// Something a keyboard shortcut can do in the editor.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+        pub enum Action {
+            $($action,)*
+        }
+
+        impl Action {
+            pub const ALL: &'static [Self] = &[$(Self::$action,)*];
+
+            /// Name used in the options file.
+            pub fn id(self) -> &'static str {
+                match self {
+                    $(Self::$action => $id,)*
+                }
+            }
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$action => $name,)*
+                }
+            }
+
+            pub fn default_binding(self) -> Binding {
+                match self {
+                    $(Self::$action => Binding::new(Modifiers::$modifiers, Key::$key),)*
+                }
+            }
+        }
+    };
+}
+
+actions! {
+    MoveUp, "move_up", "Move up", NONE + ArrowUp;
+    MoveDown, "move_down", "Move down", NONE + ArrowDown;
+    MoveLeft, "move_left", "Move left", NONE + ArrowLeft;
+    MoveRight, "move_right", "Move right", NONE + ArrowRight;
+    Shrink, "shrink", "Shrink", NONE + A;
+    Grow, "grow", "Grow", NONE + S;
+    Color0, "color_0", "Color 0", NONE + Num0;
+    Color1, "color_1", "Color 1", NONE + Num1;
+    Color2, "color_2", "Color 2", NONE + Num2;
+    Color3, "color_3", "Color 3", NONE + Num3;
+    Color4, "color_4", "Color 4", NONE + Num4;
+    Color5, "color_5", "Color 5", NONE + Num5;
+    Color6, "color_6", "Color 6", NONE + Num6;
+    Color7, "color_7", "Color 7", NONE + Num7;
+    Color8, "color_8", "Color 8", NONE + Num8;
+    Color9, "color_9", "Color 9", NONE + Num9;
+    Color10, "color_10", "Color 10", SHIFT + Num0;
+    Color11, "color_11", "Color 11", SHIFT + Num1;
+    Color12, "color_12", "Color 12", SHIFT + Num2;
+    Color13, "color_13", "Color 13", SHIFT + Num3;
+    Color14, "color_14", "Color 14", SHIFT + Num4;
+    Color15, "color_15", "Color 15", SHIFT + Num5;
+    PreviousColor, "previous_color", "Previous color", NONE + Minus;
+    NextColor, "next_color", "Next color", NONE + PlusEquals;
+    NextShape, "next_shape", "Select next shape", NONE + Enter;
+    PreviousShape, "previous_shape", "Select previous shape", SHIFT + Enter;
+    AddCircle, "add_circle", "Add circle", NONE + Z;
+    AddSquare, "add_square", "Add square", NONE + X;
+    AddTriangle, "add_triangle", "Add triangle", NONE + C;
+    RemoveShapes, "remove_shapes", "Remove selected shapes", NONE + Delete;
+}
+
+impl Action {
+    /// The actions picking each entry of the color table, in order.
+    pub const COLORS: [Self; 16] = [
+        Self::Color0,
+        Self::Color1,
+        Self::Color2,
+        Self::Color3,
+        Self::Color4,
+        Self::Color5,
+        Self::Color6,
+        Self::Color7,
+        Self::Color8,
+        Self::Color9,
+        Self::Color10,
+        Self::Color11,
+        Self::Color12,
+        Self::Color13,
+        Self::Color14,
+        Self::Color15,
+    ];
+}
+
+/// Shortcuts the editor handles itself, which actions can't share.
+const RESERVED: [(Modifiers, Key, &str); 11] = [
+    (Modifiers::COMMAND, Key::Z, "Undo"),
+    (Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z, "Redo"),
+    (Modifiers::COMMAND, Key::S, "Save"),
+    (Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::S, "Save as"),
+    (Modifiers::COMMAND, Key::A, "Select all"),
+    (Modifiers::COMMAND, Key::C, "Copy"),
+    (Modifiers::COMMAND, Key::X, "Cut"),
+    (Modifiers::COMMAND, Key::V, "Paste"),
+    (Modifiers::COMMAND, Key::D, "Duplicate"),
+    (Modifiers::NONE, Key::Space, "Play/pause"),
+    (
+        Modifiers::COMMAND.plus(Modifiers::SHIFT),
+        Key::P,
+        "Command palette",
+    ),
+];
+
+/// Every key egui reports, for reading bindings back by name.
+const KEYS: [Key; 73] = {
+    use Key::*;
+    [
+        ArrowDown, ArrowLeft, ArrowRight, ArrowUp, Escape, Tab, Backspace, Enter, Space, Insert,
+        Delete, Home, End, PageUp, PageDown, Minus, PlusEquals, Num0, Num1, Num2, Num3, Num4, Num5,
+        Num6, Num7, Num8, Num9, A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V,
+        W, X, Y, Z, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13, F14, F15, F16, F17,
+        F18, F19, F20,
+    ]
+};
+
+/// A key along with the modifiers that have to be held with it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Binding {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl Binding {
+    /// Makes a binding, treating Ctrl and ⌘ alike.
+    pub const fn new(modifiers: Modifiers, key: Key) -> Self {
+        Self {
+            modifiers: Modifiers {
+                alt: modifiers.alt,
+                shift: modifiers.shift,
+                command: modifiers.command || modifiers.ctrl || modifiers.mac_cmd,
+                ctrl: false,
+                mac_cmd: false,
+            },
+            key,
+        }
+    }
+
+    /// Whether this was pressed since the last frame.
+    pub fn pressed(&self, input: &InputState) -> bool {
+        input.events.iter().any(|event| {
+            matches!(
+                event,
+                Event::Key { key, pressed: true, modifiers, .. }
+                    if *key == self.key && modifiers.matches(self.modifiers)
+            )
+        })
+    }
+}
+
+impl Display for Binding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.modifiers.command {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{}", self.key.name())
+    }
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (modifier_names, key_name) = s.rsplit_once('+').unwrap_or(("", s));
+        let mut modifiers = Modifiers::NONE;
+        for name in modifier_names.split('+').filter(|name| !name.is_empty()) {
+            match name.trim().to_lowercase().as_str() {
+                "ctrl" | "cmd" | "command" => modifiers.command = true,
+                "alt" | "option" => modifiers.alt = true,
+                "shift" => modifiers.shift = true,
+                _ => return Err(format!("unknown modifier {name}")),
+            }
+        }
+        let key = KEYS
+            .into_iter()
+            .find(|key| key.name().eq_ignore_ascii_case(key_name.trim()))
+            .ok_or_else(|| format!("unknown key {key_name}"))?;
+        Ok(Self::new(modifiers, key))
+    }
+}
+
+/// The binding for each action.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keymap {
+    bindings: BTreeMap<Action, Binding>,
+}
+
+impl Keymap {
+    pub fn get(&self, action: Action) -> Binding {
+        self.bindings
+            .get(&action)
+            .copied()
+            .unwrap_or_else(|| action.default_binding())
+    }
+
+    pub fn set(&mut self, action: Action, binding: Binding) {
+        self.bindings.insert(action, binding);
+    }
+
+    pub fn pressed(&self, action: Action, input: &InputState) -> bool {
+        self.get(action).pressed(input)
+    }
+
+    /// Names of whatever else `action`'s binding is used for.
+    pub fn conflicts(&self, action: Action) -> Vec<&'static str> {
+        let binding = self.get(action);
+        let actions = Action::ALL
+            .iter()
+            .filter(|other| **other != action && self.get(**other) == binding)
+            .map(|other| other.name());
+        let reserved = RESERVED
+            .into_iter()
+            .filter(|(modifiers, key, _)| Binding::new(*modifiers, *key) == binding)
+            .map(|(_, _, name)| name);
+        actions.chain(reserved).collect()
+    }
+
+    /// Whether any binding is used for more than one thing.
+    pub fn has_conflicts(&self) -> bool {
+        Action::ALL
+            .iter()
+            .any(|action| !self.conflicts(*action).is_empty())
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL
+                .iter()
+                .map(|action| (*action, action.default_binding()))
+                .collect(),
+        }
+    }
+}
+
+impl Serialize for Keymap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            Action::ALL
+                .iter()
+                .map(|action| (action.id(), self.get(*action).to_string())),
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for Keymap {
+    /// Reads bindings leniently, keeping the default for any action that's
+    /// missing or unreadable so an old or hand-edited file still loads.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let saved = BTreeMap::<String, String>::deserialize(deserializer)?;
+        let mut keymap = Self::default();
+        for action in Action::ALL {
+            if let Some(Ok(binding)) = saved.get(action.id()).map(|binding| binding.parse()) {
+                keymap.set(*action, binding);
+            }
+        }
+        Ok(keymap)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn bindings() {
+    for text in ["Up", "Shift+Enter", "Ctrl+Alt+F5", "0"] {
+        assert_eq!(text.parse::<Binding>().unwrap().to_string(), text);
+    }
+    assert_eq!(
+        "cmd + shift + z".parse(),
+        Ok(Binding::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z))
+    );
+    assert!("Hyper+Q".parse::<Binding>().is_err());
+    assert!("Ctrl+Pause".parse::<Binding>().is_err());
+
+    assert!(!Keymap::default().has_conflicts());
+
+    let mut keymap = toml::from_str::<Keymap>(
+        "move_up = \"W\"\nshrink = \"nonsense\"\nfrom_the_future = \"F1\"",
+    )
+    .unwrap();
+    assert_eq!(
+        keymap.get(Action::MoveUp),
+        Binding::new(Modifiers::NONE, Key::W)
+    );
+    assert_eq!(keymap.get(Action::Shrink), Action::Shrink.default_binding());
+    assert!(keymap.conflicts(Action::MoveUp).is_empty());
+
+    keymap.set(Action::Grow, Binding::new(Modifiers::NONE, Key::W));
+    assert_eq!(keymap.conflicts(Action::MoveUp), ["Grow"]);
+    keymap.set(Action::Grow, Binding::new(Modifiers::CTRL, Key::S));
+    assert_eq!(keymap.conflicts(Action::Grow), ["Save"]);
+    assert!(keymap.has_conflicts());
+}
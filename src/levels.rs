@@ -0,0 +1,179 @@
+use std::{
+    fs::{copy, create_dir, read_dir},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{audio::SONG_FILE_NAMES, project::Project};
+
+/// Name of the level file inside each level's folder.
+pub const LEVEL_FILE_NAME: &str = "level.json";
+
+/// A folder of levels laid out as the game expects, each in
+/// `<Name>/level.json` with its song beside it.
+pub struct LevelPack {
+    pub directory: PathBuf,
+    pub levels: Vec<Level>,
+}
+
+pub struct Level {
+    /// The name of the level's folder.
+    pub name: String,
+    pub path: PathBuf,
+    pub has_song: bool,
+}
+
+impl LevelPack {
+    /// Opens a directory of levels, or the one containing `directory` if it's
+    /// a level itself.
+    pub fn open(directory: &Path) -> io::Result<Self> {
+        let directory = if directory.join(LEVEL_FILE_NAME).is_file() {
+            directory.parent().unwrap_or(directory)
+        } else {
+            directory
+        };
+        let mut pack = Self {
+            directory: directory.to_owned(),
+            levels: Vec::new(),
+        };
+        pack.refresh()?;
+        Ok(pack)
+    }
+
+    /// Rescans the directory for levels.
+    pub fn refresh(&mut self) -> io::Result<()> {
+        self.levels.clear();
+        for entry in read_dir(&self.directory)? {
+            let folder = entry?.path();
+            let path = folder.join(LEVEL_FILE_NAME);
+            if path.is_file() {
+                self.levels.push(Level {
+                    name: folder
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned(),
+                    has_song: SONG_FILE_NAMES
+                        .iter()
+                        .any(|name| folder.join(name).is_file()),
+                    path,
+                });
+            }
+        }
+        self.levels
+            .sort_by_cached_key(|level| level.name.to_lowercase());
+        Ok(())
+    }
+
+    /// Makes a folder for a new level called `name`, returning the path to its level file.
+    pub fn create_level(&mut self, name: &str) -> io::Result<PathBuf> {
+        let name = name.trim();
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("\"{name}\" can't be used as a folder name"),
+            ));
+        }
+        let folder = self.directory.join(name);
+        create_dir(&folder)?;
+
+        let mut project = Project::default();
+        project.header.name = name.to_owned();
+        let path = folder.join(LEVEL_FILE_NAME);
+        project.save(&path)?;
+        self.refresh()?;
+        Ok(path)
+    }
+}
+
+/// Whether `level` is laid out as the game expects, in a folder of its own.
+pub fn is_level_file(level: &Path) -> bool {
+    level
+        .file_name()
+        .is_some_and(|name| name == LEVEL_FILE_NAME)
+}
+
+/// Whether `a` and `b` are the same existing file.
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    a.canonicalize()
+        .is_ok_and(|a| b.canonicalize().is_ok_and(|b| a == b))
+}
+
+/// Where `song` goes next to `level`, under the name the game looks for.
+pub fn song_destination(song: &Path, level: &Path) -> io::Result<PathBuf> {
+    let directory = level.parent().unwrap_or(Path::new("."));
+    let extension = song
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let Some(name) = SONG_FILE_NAMES
+        .into_iter()
+        .find(|name| name.ends_with(&format!(".{extension}")))
+    else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "only OGG and WAV files are supported",
+        ));
+    };
+
+    Ok(directory.join(name))
+}
+
+/// Copies `song` to its [`song_destination`], replacing anything already
+/// there, and returns where it was copied to.
+///
+/// A song of the other type is left alone, though the game may prefer it; see
+/// [`Song::find_beside`](crate::audio::Song::find_beside).
+pub fn copy_song(song: &Path, level: &Path) -> io::Result<PathBuf> {
+    let destination = song_destination(song, level)?;
+    if !same_file(song, &destination) {
+        copy(song, &destination)?;
+    }
+    Ok(destination)
+}
+
+#[cfg(test)]
+#[test]
+fn level_folders() {
+    let directory = std::env::temp_dir().join(format!("manifest-levels-{}", std::process::id()));
+    create_dir(&directory).unwrap();
+
+    let mut pack = LevelPack::open(&directory).unwrap();
+    assert!(pack.levels.is_empty());
+    let level = pack.create_level("Tutorial").unwrap();
+    pack.create_level("credits").unwrap();
+    assert!(pack.create_level("Tutorial").is_err());
+    assert!(pack.create_level("../Escape").is_err());
+    assert_eq!(Project::load(&level).unwrap().header.name, "Tutorial");
+    assert!(is_level_file(&level));
+    assert!(!is_level_file(&directory.join("Tutorial.json")));
+
+    let wav = directory.join("Track 1.wav");
+    crate::audio::write_sine_wav(&wav, 440.0, 0.1);
+    assert_eq!(
+        copy_song(&wav, &level).unwrap(),
+        directory.join("Tutorial").join("song.wav")
+    );
+    assert!(copy_song(&level, &level).is_err());
+    let copied = song_destination(&wav, &level).unwrap();
+    assert!(same_file(&copied, &copy_song(&copied, &level).unwrap()));
+    assert!(!same_file(&wav, &copied));
+
+    // An OGG already there stays, and wins over the new WAV
+    let ogg = directory.join("Tutorial").join("song.ogg");
+    std::fs::write(&ogg, b"").unwrap();
+    copy_song(&wav, &level).unwrap();
+    assert_eq!(crate::audio::Song::find_beside(&level), Some(ogg.clone()));
+    std::fs::remove_file(ogg).unwrap();
+
+    // Opening a level's own folder opens the pack around it
+    let pack = LevelPack::open(level.parent().unwrap()).unwrap();
+    let levels = pack
+        .levels
+        .iter()
+        .map(|level| (level.name.as_str(), level.has_song))
+        .collect::<Vec<_>>();
+    assert_eq!(levels, [("credits", false), ("Tutorial", true)]);
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
@@ -1,48 +1,1078 @@
+mod audio;
+mod cli;
+mod history;
+mod keymap;
+mod levels;
 mod options;
+mod palette;
+mod play_test;
+mod playback;
 mod project;
+mod render;
 mod shape;
+mod tempo;
+mod validate;
 
 use std::{
-    fs::{read_to_string, File},
-    io::Write,
-    num::NonZeroU16,
+    collections::BTreeSet,
+    fs::create_dir_all,
+    num::{NonZeroU16, NonZeroU8},
+    path::{Path, PathBuf},
+    process::exit,
+    thread::{self, JoinHandle},
 };
 
+use audio::Song;
 use eframe::{App, CreationContext};
-use egui::{DragValue, Key, ScrollArea, Vec2};
+use egui::{
+    Button, Color32, ComboBox, DragValue, Event, Key, Modifiers, Pos2, Rect, RichText, ScrollArea,
+    Vec2,
+};
+use history::History;
+use keymap::Action;
+use levels::LevelPack;
 use options::{Options, OptionsMenu};
-use project::Project;
+use palette::{Command, CommandPalette};
+use play_test::PlayTest;
+use playback::{Playback, State};
+use project::{Project, BACKGROUND_EFFECTS};
+use render::{Animation, Image, DEFAULT_SIZE};
 use rfd::FileDialog;
-use shape::Shape;
+use shape::{Move, Shape, ShapeType};
+use tempo::{Estimate, TapTempo};
+use validate::{validate, Severity};
 
 const BUILD_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Names the level browser's new level field, so the command palette can focus it.
+const NEW_LEVEL_FIELD: &str = "new_level_name";
 
 #[derive(Default)]
 struct Main {
     options: Options,
     options_menu: Option<OptionsMenu>,
+    command_palette: Option<CommandPalette>,
     project: Project,
+    /// The file the project was last loaded from or saved to.
+    path: Option<PathBuf>,
+    /// The folder of levels shown in the browser, if one is open.
+    level_pack: Option<LevelPack>,
+    new_level_name: String,
+    history: History,
 
     selected_shape: usize,
+    /// Every selected shape including `selected_shape`, or empty if it's the only one.
+    selection: BTreeSet<usize>,
+    selected_step: usize,
+    selected_auto_shape: Option<usize>,
+    canvas_drag: Option<CanvasDrag>,
+    /// Whether the selected shapes are being dragged to a new place in the shapes panel.
+    reordering: bool,
+
+    /// The project as of the last save or load, to tell whether there are unsaved changes.
+    saved: Project,
+    unsaved_prompt: Option<Discard>,
+    allow_close: bool,
+    title: String,
+
+    playback: Playback,
+    /// The song last loaded into `playback`, to notice when the project's changes.
+    loaded_song: Option<PathBuf>,
+    tempo_estimate: Option<Estimate>,
+    tempo_analysis: Option<JoinHandle<Option<Estimate>>>,
+    tap_tempo: TapTempo,
+    play_test: Option<PlayTest>,
+    show_play_log: bool,
+
+    /// An image or animation being written in the background.
+    export: Option<JoinHandle<Result<(), String>>>,
+
+    show_problems: bool,
+    invalid_save: Option<InvalidSave>,
+
+    /// A song waiting on whether to replace the one already beside the level.
+    song_copy: Option<SongCopy>,
+
+    /// Message shown in an error dialog until dismissed.
+    error: Option<String>,
+    /// Like `error`, but for something that worked with a catch.
+    warning: Option<String>,
+}
+
+#[derive(Clone)]
+struct SongCopy {
+    song: PathBuf,
+    destination: PathBuf,
+    level: PathBuf,
+    /// Whether the song is being attached, rather than copied while saving.
+    attach: bool,
+}
+
+/// An action that throws away the current project.
+#[derive(Clone)]
+enum Discard {
+    NewFile,
+    LoadFile,
+    /// Opening a level from the level browser.
+    Open(PathBuf),
+    Close,
+}
+
+/// A save held back because the level has errors, until the user decides.
+#[derive(Clone)]
+struct InvalidSave {
+    errors: usize,
+    save: HeldSave,
+}
+
+#[derive(Clone)]
+enum HeldSave {
+    /// Saving to a file, then carrying on with the discard it was for, if any.
+    File(PathBuf, Option<Discard>),
+    /// Saving the level to play test it.
+    PlayTest,
+}
+
+#[derive(Clone, Copy)]
+enum CanvasDrag {
+    /// Moving the selected shape, which sits `offset` away from the pointer.
+    Move { offset: Vec2 },
+    /// Dragging the selected shape's size handle.
+    Resize,
+    /// Dragging out a box to select the shapes it touches.
+    Select {
+        start: Pos2,
+        end: Pos2,
+        extend: bool,
+    },
 }
 
 impl Main {
-    pub fn new(ctx: &CreationContext, project: Option<Project>) -> Self {
+    pub fn new(ctx: &CreationContext, file: Option<(PathBuf, Project)>) -> Self {
         let options = Options::load();
         ctx.egui_ctx.set_visuals(match options.dark_theme {
             true => egui::Visuals::dark(),
             false => egui::Visuals::light(),
         });
 
-        Self {
-            project: project.unwrap_or_default(),
+        let (path, project) = file.unzip();
+        let mut main = Self {
+            options,
             ..Default::default()
+        };
+        main.set_project(project.unwrap_or_default(), path);
+        main
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.project != self.saved
+    }
+
+    /// Runs `discard`, asking to save first if there are unsaved changes.
+    fn discard_project(&mut self, discard: Discard, frame: &mut eframe::Frame) {
+        if self.is_dirty() {
+            self.unsaved_prompt = Some(discard);
+        } else {
+            self.run_discard(discard, frame);
+        }
+    }
+
+    fn run_discard(&mut self, discard: Discard, frame: &mut eframe::Frame) {
+        match discard {
+            Discard::NewFile => self.set_project(Project::default(), None),
+            Discard::LoadFile => {
+                if let Some(path) = FileDialog::new().add_filter("json", &["json"]).pick_file() {
+                    self.load_file(path);
+                }
+            }
+            Discard::Open(path) => self.load_file(path),
+            Discard::Close => {
+                self.allow_close = true;
+                frame.close();
+            }
+        }
+    }
+
+    fn load_file(&mut self, path: PathBuf) {
+        match Project::load(&path) {
+            Ok(project) => self.set_project(project, Some(path)),
+            Err(err) => {
+                self.error = Some(format!("Could not load {}:\n{err}", path.display()));
+            }
+        }
+    }
+
+    /// Asks for a folder of levels to show in the browser, opening the level
+    /// if a level's own folder was picked.
+    fn open_folder(&mut self, frame: &mut eframe::Frame) {
+        let Some(directory) = FileDialog::new().pick_folder() else {
+            return;
+        };
+        match LevelPack::open(&directory) {
+            Ok(pack) => {
+                self.level_pack = Some(pack);
+                let level = directory.join(levels::LEVEL_FILE_NAME);
+                if level.is_file() {
+                    self.discard_project(Discard::Open(level), frame);
+                }
+            }
+            Err(err) => {
+                self.error = Some(format!("Could not open {}:\n{err}", directory.display()));
+            }
+        }
+    }
+
+    /// Uses the song at `path`, copying it beside the level first if the
+    /// level is saved in a level folder.
+    fn attach_song(&mut self, path: PathBuf) {
+        match self
+            .path
+            .clone()
+            .filter(|level| levels::is_level_file(level))
+        {
+            Some(level) => self.place_song(path, level, true),
+            None => self.project.song = Some(path),
+        }
+    }
+
+    /// Copies `song` beside `level`, first asking if that would replace a
+    /// different file. With `attach` the project switches to the copy,
+    /// otherwise it takes whichever song ends up beside the level.
+    fn place_song(&mut self, song: PathBuf, level: PathBuf, attach: bool) {
+        match levels::song_destination(&song, &level) {
+            Ok(destination) if destination.exists() && !levels::same_file(&song, &destination) => {
+                self.song_copy = Some(SongCopy {
+                    song,
+                    destination,
+                    level,
+                    attach,
+                });
+            }
+            Ok(_) => self.copy_song(&song, &level, attach),
+            Err(err) => self.error = Some(format!("Could not copy {}:\n{err}", song.display())),
+        }
+    }
+
+    fn copy_song(&mut self, song: &Path, level: &Path, attach: bool) {
+        match levels::copy_song(song, level) {
+            Ok(copied) => {
+                if let Some(other) = Song::find_beside(level).filter(|other| *other != copied) {
+                    self.warning = Some(format!(
+                        "Open Manifold will play {} rather than the new song until it's removed.",
+                        other.display()
+                    ));
+                }
+                // The file may have changed without its path changing
+                self.loaded_song = None;
+                if attach {
+                    self.project.song = Some(copied);
+                } else {
+                    self.adopt_song(Some(copied));
+                }
+                if let Some(pack) = &mut self.level_pack {
+                    let _ = pack.refresh();
+                }
+            }
+            Err(err) => self.error = Some(format!("Could not copy {}:\n{err}", song.display())),
+        }
+    }
+
+    /// Switches to `song` without it counting as a change, for when the song
+    /// beside a level was just settled by saving it.
+    fn adopt_song(&mut self, song: Option<PathBuf>) {
+        self.project.song = song.clone();
+        self.saved.song = song;
+    }
+
+    fn set_project(&mut self, project: Project, path: Option<PathBuf>) {
+        self.path = path;
+        self.history = History::new(&project);
+        self.saved = project.clone();
+        self.project = project;
+        self.selected_shape = 0;
+        self.selection.clear();
+        self.selected_auto_shape = None;
+        self.playback.stop();
+    }
+
+    /// Loads the project's song into playback if it has changed, such as
+    /// after attaching one or undoing that.
+    fn sync_song(&mut self) {
+        if self.project.song == self.loaded_song {
+            return;
+        }
+        self.loaded_song = self.project.song.clone();
+        let song = self.loaded_song.as_deref().and_then(|path| {
+            Song::load(path)
+                .map_err(|err| {
+                    self.error = Some(format!("Could not load {}:\n{err}", path.display()));
+                })
+                .ok()
+        });
+        self.playback.set_song(song, &self.project);
+    }
+
+    /// Saves the project to its current file, returning whether it was saved.
+    fn save_file(&mut self) -> bool {
+        match self.path.clone() {
+            Some(path) => self.save_to(path),
+            None => self.save_file_as(),
+        }
+    }
+
+    /// Asks where to save the project, returning whether it was saved.
+    fn save_file_as(&mut self) -> bool {
+        let mut dialog = FileDialog::new().add_filter("json", &["json"]);
+        if let Some(path) = &self.path {
+            if let Some(directory) = path.parent() {
+                dialog = dialog.set_directory(directory);
+            }
+            if let Some(name) = path.file_name() {
+                dialog = dialog.set_file_name(&name.to_string_lossy());
+            }
+        }
+        let Some(mut path) = dialog.save_file() else {
+            return false;
+        };
+        if path.extension().is_none() {
+            path.set_extension("json");
+        }
+        self.save_to(path)
+    }
+
+    /// Holds back `save` to ask first if the level has errors, returning
+    /// whether it can go ahead.
+    fn check_before_saving(&mut self, save: HeldSave) -> bool {
+        let errors = validate(&self.project)
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .count();
+        if errors > 0 {
+            self.invalid_save = Some(InvalidSave { errors, save });
+        }
+        errors == 0
+    }
+
+    /// Saves to `path`, first asking for confirmation if the level has errors.
+    fn save_to(&mut self, path: PathBuf) -> bool {
+        self.check_before_saving(HeldSave::File(path.clone(), None)) && self.save_unchecked(path)
+    }
+
+    fn save_unchecked(&mut self, path: PathBuf) -> bool {
+        match self.project.save(&path) {
+            Ok(()) => {
+                self.saved = self.project.clone();
+                // A song attached before now may not be beside this level yet
+                if let Some(song) = self.project.song.clone().filter(|song| {
+                    levels::is_level_file(&path)
+                        && !levels::song_destination(song, &path)
+                            .is_ok_and(|destination| levels::same_file(song, &destination))
+                }) {
+                    self.place_song(song, path.clone(), false);
+                }
+                self.path = Some(path);
+                if let Some(pack) = &mut self.level_pack {
+                    let _ = pack.refresh();
+                }
+                true
+            }
+            Err(err) => {
+                self.error = Some(format!("Could not save {}:\n{err}", path.display()));
+                false
+            }
+        }
+    }
+
+    /// Saves the project and launches it in Open Manifold, first asking for
+    /// confirmation if the level has errors.
+    fn start_play_test(&mut self, ctx: &egui::Context) {
+        if self.options.executable_path.is_empty() {
+            self.error = Some("Set the game executable in Options first".to_owned());
+        } else if self.check_before_saving(HeldSave::PlayTest) {
+            self.launch_play_test(ctx);
+        }
+    }
+
+    /// Saves the project without checking it and launches it.
+    ///
+    /// Unsaved projects are written to a temporary file rather than asking for a path.
+    fn launch_play_test(&mut self, ctx: &egui::Context) {
+        let level = match self.path.clone() {
+            Some(path) => {
+                if !self.save_unchecked(path.clone()) {
+                    return;
+                }
+                path
+            }
+            None => {
+                let level = std::env::temp_dir()
+                    .join("manifest-play-test")
+                    .join("level.json");
+                let saved =
+                    create_dir_all(level.parent().unwrap()).and_then(|_| self.project.save(&level));
+                if let Err(err) = saved {
+                    self.error = Some(format!("Could not save {}:\n{err}", level.display()));
+                    return;
+                }
+                level
+            }
+        };
+
+        if let Some(play_test) = &mut self.play_test {
+            play_test.kill();
+        }
+        let ctx = ctx.clone();
+        match PlayTest::spawn(
+            Path::new(&self.options.executable_path),
+            &level,
+            move || ctx.request_repaint(),
+        ) {
+            Ok(play_test) => {
+                self.play_test = Some(play_test);
+                self.show_play_log = true;
+            }
+            Err(err) => {
+                self.error = Some(format!(
+                    "Could not start {}:\n{err}",
+                    self.options.executable_path
+                ));
+            }
+        }
+    }
+
+    /// Asks where to save a picture of the level at the selected step, or an
+    /// animation of the whole level, then renders it in the background.
+    fn export(&mut self, animate: bool, ctx: &egui::Context) {
+        if self.export.is_some() {
+            return;
+        }
+        let extension = if animate { "gif" } else { "png" };
+        let mut dialog = FileDialog::new().add_filter(extension, &[extension]);
+        if let Some(directory) = self.path.as_deref().and_then(Path::parent) {
+            dialog = dialog.set_directory(directory);
+        }
+        let Some(mut path) = dialog.save_file() else {
+            return;
+        };
+        if path.extension().is_none() {
+            path.set_extension(extension);
+        }
+
+        let project = self.project.clone();
+        let beat = if self.playback.is_active() {
+            self.playback.beat
+        } else {
+            project
+                .step_beat(self.selected_shape, self.selected_step)
+                .unwrap_or_default()
+        };
+        let ctx = ctx.clone();
+        self.export = Some(thread::spawn(move || {
+            let result = if animate {
+                Animation::new(&project, DEFAULT_SIZE)
+                    .save_gif(&path)
+                    .map_err(|err| err.to_string())
+            } else {
+                Image::at_beat(&project, beat, DEFAULT_SIZE)
+                    .save_png(&path)
+                    .map_err(|err| err.to_string())
+            };
+            ctx.request_repaint();
+            result.map_err(|err| format!("Could not export {}:\n{err}", path.display()))
+        }));
+    }
+
+    /// Asks for a song to attach.
+    fn pick_song(&mut self) {
+        if let Some(path) = FileDialog::new()
+            .add_filter("Audio", &["ogg", "wav"])
+            .pick_file()
+        {
+            self.attach_song(path);
+        }
+    }
+
+    /// Estimates the tempo of the song in the background.
+    fn analyse_tempo(&mut self, ctx: &egui::Context) {
+        if self.tempo_analysis.is_some() {
+            return;
+        }
+        let Some(song) = self.playback.song().cloned() else {
+            self.error = Some("Attach a song first".to_owned());
+            return;
+        };
+        let ctx = ctx.clone();
+        self.tempo_estimate = None;
+        self.tempo_analysis = Some(thread::spawn(move || {
+            let estimate = tempo::analyse(&song);
+            ctx.request_repaint();
+            estimate
+        }));
+    }
+
+    fn undo(&mut self) {
+        self.history.undo(&mut self.project);
+        self.clamp_selection();
+    }
+
+    fn redo(&mut self) {
+        self.history.redo(&mut self.project);
+        self.clamp_selection();
+    }
+
+    /// Indices of the selected shapes, in order.
+    fn selected_shapes(&self) -> Vec<usize> {
+        let mut selected = self.selection.clone();
+        selected.insert(self.selected_shape);
+        selected
+            .into_iter()
+            .filter(|i| *i < self.project.shapes.len())
+            .collect()
+    }
+
+    /// How many shapes the canvas shows, which is all of them up to the last selected.
+    fn visible_shapes(&self) -> usize {
+        self.selection
+            .last()
+            .map_or(self.selected_shape, |last| self.selected_shape.max(*last))
+            + 1
+    }
+
+    /// Selects shape `i`, toggling it in the selection with Ctrl or
+    /// selecting the range up to it with Shift.
+    fn select_shape(&mut self, i: usize, modifiers: Modifiers) {
+        if modifiers.shift {
+            let range = self.selected_shape.min(i)..=self.selected_shape.max(i);
+            self.selection.extend(range);
+        } else if modifiers.command {
+            self.selection.insert(self.selected_shape);
+            if self.selection.contains(&i) {
+                if self.selection.len() > 1 {
+                    self.selection.remove(&i);
+                    if i == self.selected_shape {
+                        self.selected_shape = *self.selection.last().unwrap();
+                        self.selected_auto_shape = None;
+                    }
+                }
+                self.tidy_selection();
+                return;
+            }
+            self.selection.insert(i);
+        } else {
+            self.selection.clear();
+        }
+        self.selected_shape = i;
+        self.selected_auto_shape = None;
+        self.tidy_selection();
+    }
+
+    /// Empties `selection` if it only holds `selected_shape`.
+    fn tidy_selection(&mut self) {
+        if self.selection.len() == 1 {
+            self.selection.clear();
+        }
+    }
+
+    fn select_all(&mut self) {
+        if !self.project.shapes.is_empty() {
+            self.selection = (0..self.project.shapes.len()).collect();
+            self.selected_shape = self.selected_shape.min(self.project.shapes.len() - 1);
+            self.tidy_selection();
+        }
+    }
+
+    /// Applies `edit` to every selected shape.
+    fn edit_selected(&mut self, mut edit: impl FnMut(&mut Shape)) {
+        for i in self.selected_shapes() {
+            edit(&mut self.project.shapes[i]);
+        }
+    }
+
+    /// Moves the selected shapes by up to `delta`, keeping their layout rather
+    /// than squashing them against the edges.
+    fn translate_selected(&mut self, delta: Vec2) {
+        let (min, max) = self.selected_shapes().into_iter().fold(
+            (Vec2::splat(14.0), Vec2::ZERO),
+            |(min, max), i| {
+                let pos = self.project.shapes[i].pos;
+                (min.min(pos), max.max(pos))
+            },
+        );
+        let delta = delta.max(-min).min(Vec2::splat(14.0) - max);
+        self.edit_selected(|shape| shape.pos += delta);
+    }
+
+    /// Grows the selected shapes by up to `delta`, stopping together once
+    /// any of them is as small or large as a shape can be.
+    fn resize_selected(&mut self, delta: f32) {
+        let (min, max) =
+            self.selected_shapes()
+                .into_iter()
+                .fold((7.0, 0.0), |(min, max): (f32, f32), i| {
+                    let size = self.project.shapes[i].size;
+                    (min.min(size), max.max(size))
+                });
+        let delta = delta.max(-min).min(7.0 - max);
+        self.edit_selected(|shape| shape.size += delta);
+    }
+
+    /// Inserts `shapes` after the selection, or at the end if there isn't
+    /// one, and selects them instead.
+    fn insert_shapes(&mut self, shapes: Vec<Shape>) {
+        if shapes.is_empty() {
+            return;
+        }
+        let index = self
+            .selected_shapes()
+            .last()
+            .map_or(self.project.shapes.len(), |last| last + 1);
+        let count = shapes.len();
+        self.project.shapes.splice(index..index, shapes);
+        self.selected_shape = index;
+        self.selection = (index..index + count).collect();
+        self.tidy_selection();
+        self.selected_auto_shape = None;
+    }
+
+    fn copy_selected(&self, ctx: &egui::Context) {
+        let selected = self
+            .selected_shapes()
+            .into_iter()
+            .map(|i| &self.project.shapes[i])
+            .collect::<Vec<_>>();
+        if !selected.is_empty() {
+            ctx.output_mut(|output| output.copied_text = Shape::to_clipboard(&selected));
+        }
+    }
+
+    fn cut_selected(&mut self, ctx: &egui::Context) {
+        self.copy_selected(ctx);
+        self.remove_selected();
+    }
+
+    fn paste(&mut self, text: &str) {
+        match Shape::from_clipboard(text) {
+            Ok(shapes) => self.insert_shapes(shapes),
+            Err(err) => self.error = Some(format!("Could not paste shapes:\n{err}")),
+        }
+    }
+
+    fn duplicate_selected(&mut self) {
+        let copies = self
+            .selected_shapes()
+            .into_iter()
+            .map(|i| self.project.shapes[i].clone())
+            .collect();
+        self.insert_shapes(copies);
+    }
+
+    /// Moves the selected shapes together to `target` in the play order.
+    fn move_selected(&mut self, target: usize) {
+        let selected = self.selected_shapes();
+        let Some(primary) = selected.iter().position(|i| *i == self.selected_shape) else {
+            return;
+        };
+        let moved = self.project.move_shapes(&selected, target);
+        self.selected_shape = moved.start + primary;
+        self.selection = match moved.len() {
+            1 => BTreeSet::new(),
+            _ => moved.collect(),
+        };
+    }
+
+    /// Selects shape `i` when its thumbnail is clicked, or starts moving the
+    /// selection once the thumbnail has been dragged `travel` from where it
+    /// was pressed, selecting it first as a click would if it wasn't already.
+    fn thumbnail_input(
+        &mut self,
+        i: usize,
+        clicked: bool,
+        travel: Option<Vec2>,
+        modifiers: Modifiers,
+    ) {
+        // Pressing counts as a drag straight away, so a click would start
+        // reordering too without waiting for the pointer to move
+        const REORDER_DISTANCE: f32 = 6.0;
+        if !self.reordering && travel.is_some_and(|travel| travel.length() > REORDER_DISTANCE) {
+            if !self.selected_shapes().contains(&i) {
+                self.select_shape(i, modifiers);
+            }
+            self.reordering = true;
+        }
+        if clicked {
+            self.select_shape(i, modifiers);
+        }
+    }
+
+    /// Moves the selected shapes to where they were dropped, in front of the
+    /// shape that was at `gap`.
+    fn drop_selected(&mut self, gap: usize) {
+        let before = self
+            .selected_shapes()
+            .into_iter()
+            .filter(|i| *i < gap)
+            .count();
+        self.move_selected(gap - before);
+    }
+
+    /// Gives the selected shape a sequence of moves if it doesn't have one.
+    fn add_sequence(&mut self) {
+        if let Some(shape) = self.project.shapes.get_mut(self.selected_shape) {
+            if shape.moves.is_none() {
+                shape.moves = Some(vec![]);
+                self.selected_step = 0;
+            }
+        }
+    }
+
+    fn remove_sequence(&mut self) {
+        if let Some(shape) = self.project.shapes.get_mut(self.selected_shape) {
+            shape.moves = None;
+        }
+    }
+
+    /// Adds an auto shape of type `ty` to the selected shape, in its color.
+    fn add_auto_shape(&mut self, ty: ShapeType) {
+        if let Some(shape) = self.project.shapes.get_mut(self.selected_shape) {
+            shape.auto_shapes.push(Shape {
+                ty,
+                color: shape.color,
+                ..Default::default()
+            });
+            self.selected_auto_shape = Some(shape.auto_shapes.len() - 1);
+        }
+    }
+
+    fn remove_auto_shape(&mut self) {
+        let shape = self.project.shapes.get_mut(self.selected_shape);
+        if let (Some(shape), Some(i)) = (shape, self.selected_auto_shape) {
+            if i < shape.auto_shapes.len() {
+                shape.auto_shapes.remove(i);
+            }
         }
+        self.selected_auto_shape = None;
+    }
+
+    fn remove_selected(&mut self) {
+        let selected = self.selected_shapes();
+        for i in selected.iter().rev() {
+            self.project.shapes.remove(*i);
+        }
+        if let Some(first) = selected.first() {
+            self.selected_shape = first.saturating_sub(1);
+        }
+        self.selection.clear();
+        self.selected_auto_shape = None;
+    }
+
+    fn run_action(&mut self, action: Action) {
+        let colors = self.project.header.color_table.len();
+        match action {
+            Action::MoveUp => self.translate_selected(-Vec2::Y),
+            Action::MoveDown => self.translate_selected(Vec2::Y),
+            Action::MoveLeft => self.translate_selected(-Vec2::X),
+            Action::MoveRight => self.translate_selected(Vec2::X),
+            Action::Shrink => self.resize_selected(-1.0),
+            Action::Grow => self.resize_selected(1.0),
+            Action::PreviousColor => {
+                self.edit_selected(|shape| shape.color = (shape.color + colors - 1) % colors)
+            }
+            Action::NextColor => {
+                self.edit_selected(|shape| shape.color = (shape.color + 1) % colors)
+            }
+            Action::NextShape | Action::PreviousShape => {
+                let count = self.project.shapes.len();
+                if count > 0 {
+                    let step = if action == Action::NextShape {
+                        1
+                    } else {
+                        count - 1
+                    };
+                    self.select_shape((self.selected_shape + step) % count, Modifiers::NONE);
+                }
+            }
+            Action::AddCircle | Action::AddSquare | Action::AddTriangle => {
+                let ty = match action {
+                    Action::AddCircle => ShapeType::Circle,
+                    Action::AddSquare => ShapeType::Square,
+                    _ => ShapeType::Triangle,
+                };
+                let color = self
+                    .project
+                    .shapes
+                    .get(self.selected_shape)
+                    .map(|shape| shape.color)
+                    .unwrap_or_default();
+                self.insert_shapes(vec![Shape {
+                    ty,
+                    color,
+                    ..Default::default()
+                }]);
+            }
+            Action::RemoveShapes => self.remove_selected(),
+            _ => {
+                if let Some(color) = Action::COLORS.iter().position(|color| *color == action) {
+                    self.edit_selected(|shape| shape.color = color);
+                }
+            }
+        }
+    }
+
+    /// Runs something picked from the command palette.
+    fn run_command(&mut self, command: Command, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let selected = self.selected_shapes();
+        match command {
+            Command::NewFile => self.discard_project(Discard::NewFile, frame),
+            Command::LoadFile => self.discard_project(Discard::LoadFile, frame),
+            Command::OpenFolder => self.open_folder(frame),
+            Command::Save => {
+                self.save_file();
+            }
+            Command::SaveAs => {
+                self.save_file_as();
+            }
+            Command::ExportImage | Command::ExportAnimation => {
+                self.export(command == Command::ExportAnimation, ctx)
+            }
+            Command::Undo => self.undo(),
+            Command::Redo => self.redo(),
+            Command::SelectAll => self.select_all(),
+            Command::Copy => self.copy_selected(ctx),
+            Command::Cut => self.cut_selected(ctx),
+            Command::Duplicate => self.duplicate_selected(),
+            Command::MoveToStart => self.move_selected(0),
+            Command::MoveEarlier | Command::MoveLater | Command::MoveToEnd => {
+                let Some(first) = selected.first() else {
+                    return;
+                };
+                self.move_selected(match command {
+                    Command::MoveEarlier => first.saturating_sub(1),
+                    Command::MoveLater => first + 1,
+                    _ => self.project.shapes.len() - selected.len(),
+                });
+            }
+            Command::PlayPause => self.playback.toggle(&self.project),
+            Command::Stop => self.playback.stop(),
+            Command::PlayTest => self.start_play_test(ctx),
+            Command::Problems => self.show_problems = true,
+            Command::Options => self.options_menu = Some(OptionsMenu::new(self.options.clone())),
+            Command::AttachSong => self.pick_song(),
+            Command::DetachSong => self.project.song = None,
+            Command::DetectTempo => self.analyse_tempo(ctx),
+            Command::TapTempo => self.tap_tempo.tap(),
+            Command::NewLevel => match self.level_pack {
+                Some(_) => ctx.memory_mut(|mem| mem.request_focus(egui::Id::new(NEW_LEVEL_FIELD))),
+                None => self.error = Some("Open a level folder first".to_owned()),
+            },
+            Command::AddSequence => self.add_sequence(),
+            Command::RemoveSequence => self.remove_sequence(),
+            Command::RemoveAutoShape => self.remove_auto_shape(),
+            Command::Action(action) => self.run_action(action),
+            Command::SetShapeType(ty) => self.edit_selected(|shape| shape.ty = ty),
+            Command::AddAutoShape(ty) => self.add_auto_shape(ty),
+        }
+    }
+
+    /// Selects, moves and resizes shapes with the mouse on the main canvas.
+    fn canvas_input(&mut self, ui: &egui::Ui, response: &egui::Response) {
+        const HANDLE_SIZE: f32 = 10.0;
+
+        let to_screen = Project::to_screen(response.rect);
+        let from_screen = to_screen.inverse();
+        let selection_stroke = ui.visuals().selection.stroke;
+        if self.selection.len() > 1 {
+            for i in self.selected_shapes() {
+                let bounds = to_screen.transform_rect(self.project.shapes[i].bounds());
+                ui.painter().rect_stroke(bounds, 0.0, selection_stroke);
+            }
+        }
+        let handle = self.project.shapes.get(self.selected_shape).map(|shape| {
+            Rect::from_center_size(to_screen * shape.bounds().max, Vec2::splat(HANDLE_SIZE))
+        });
+        if let Some(handle) = handle {
+            let visuals = ui.visuals().widgets.active;
+            ui.painter()
+                .rect(handle, 0.0, visuals.bg_fill, visuals.fg_stroke);
+        }
+
+        let Some(pointer) = response.interact_pointer_pos() else {
+            self.finish_canvas_drag();
+            return;
+        };
+        let grid_pointer = from_screen * pointer;
+        let origin = ui
+            .input(|input| input.pointer.press_origin())
+            .unwrap_or(pointer);
+        // The palette strip handles its own clicks
+        let on_palette = Self::palette_rect(response.rect).contains(origin);
+        if (response.drag_started() || response.clicked()) && !on_palette {
+            let modifiers = ui.input(|input| input.modifiers);
+            let extend = modifiers.shift || modifiers.command;
+            let grid_origin = from_screen * origin;
+            self.canvas_drag = if handle.is_some_and(|handle| handle.expand(2.0).contains(origin)) {
+                Some(CanvasDrag::Resize)
+            } else if let Some(i) = self.project.shape_at(grid_origin, self.visible_shapes()) {
+                if extend {
+                    // Pressing counts as starting a drag too, so only toggle
+                    // on release or a click would toggle twice
+                    if response.clicked() {
+                        self.select_shape(i, modifiers);
+                    }
+                    None
+                } else {
+                    // Dragging any selected shape moves the whole selection
+                    if !self.selected_shapes().contains(&i) {
+                        self.selection.clear();
+                    }
+                    self.selected_shape = i;
+                    self.selected_auto_shape = None;
+                    Some(CanvasDrag::Move {
+                        offset: self.project.shapes[i].pos - grid_origin.to_vec2(),
+                    })
+                }
+            } else {
+                Some(CanvasDrag::Select {
+                    start: grid_origin,
+                    end: grid_origin,
+                    extend,
+                })
+            };
+        }
+
+        match &mut self.canvas_drag {
+            Some(CanvasDrag::Move { offset })
+                if self.selected_shape < self.project.shapes.len() =>
+            {
+                let target = (grid_pointer.to_vec2() + *offset).round();
+                self.translate_selected(target - self.project.shapes[self.selected_shape].pos);
+            }
+            Some(CanvasDrag::Resize) if self.selected_shape < self.project.shapes.len() => {
+                let shape = &self.project.shapes[self.selected_shape];
+                let distance = (grid_pointer - shape.bounds().center()).abs().max_elem();
+                let delta = (distance - 0.5).round().clamp(0.0, 7.0) - shape.size;
+                self.resize_selected(delta);
+            }
+            Some(CanvasDrag::Select { start, end, .. }) => {
+                *end = grid_pointer;
+                let rect = to_screen.transform_rect(Rect::from_two_pos(*start, *end));
+                ui.painter().rect(
+                    rect,
+                    0.0,
+                    selection_stroke.color.linear_multiply(0.2),
+                    selection_stroke,
+                );
+            }
+            _ => {}
+        }
+        if !response.dragged() {
+            self.finish_canvas_drag();
+        }
+    }
+
+    /// Ends the current drag on the canvas, selecting the shapes in the box if it was a box selection.
+    fn finish_canvas_drag(&mut self) {
+        let Some(CanvasDrag::Select { start, end, extend }) = self.canvas_drag.take() else {
+            return;
+        };
+        let rect = Rect::from_two_pos(start, end);
+        let touching = self
+            .project
+            .shapes
+            .iter()
+            .take(self.visible_shapes())
+            .enumerate()
+            .filter(|(_, shape)| shape.bounds().intersects(rect))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if extend {
+            self.selection.insert(self.selected_shape);
+        } else {
+            self.selection.clear();
+        }
+        if let Some(last) = touching.last() {
+            if !extend {
+                self.selected_shape = *last;
+                self.selected_auto_shape = None;
+            }
+            self.selection.extend(touching);
+        }
+        self.tidy_selection();
+    }
+
+    /// Where the palette strip sits on a canvas covering `canvas`.
+    fn palette_rect(canvas: Rect) -> Rect {
+        Project::to_screen(canvas).transform_rect(Rect::from_min_size(
+            Pos2::new(0.0, 15.4),
+            Vec2::new(15.0, 0.8),
+        ))
+    }
+
+    /// Draws the color table below the grid, marking the selected shapes'
+    /// colors, and recolors the selection when a color is clicked.
+    ///
+    /// This has to come before the canvas itself, or the canvas takes the clicks.
+    fn palette_strip(&mut self, ui: &egui::Ui, canvas: Rect) {
+        let strip = Self::palette_rect(canvas);
+        let colors = self.project.header.color_table;
+        let selected = self.selected_shapes();
+        let width = strip.width() / colors.len() as f32;
+        for (i, color) in colors.into_iter().enumerate() {
+            let swatch = Rect::from_min_size(
+                strip.min + Vec2::new(i as f32 * width, 0.0),
+                Vec2::new(width, strip.height()),
+            );
+            let response = ui
+                .interact(swatch, ui.id().with(("palette", i)), egui::Sense::click())
+                .on_hover_text(format!(
+                    "Color {i} ({})",
+                    self.options.keymap.get(Action::COLORS[i])
+                ));
+            let current = selected
+                .iter()
+                .any(|shape| self.project.shapes[*shape].color == i);
+            let stroke = match current || response.hovered() {
+                true => ui.visuals().selection.stroke,
+                false => egui::Stroke::new(1.0, Color32::BLACK),
+            };
+            let rect = match current {
+                true => swatch.shrink(1.0),
+                false => swatch.shrink(3.0),
+            };
+            ui.painter().rect(
+                rect,
+                2.0,
+                Color32::from_rgb(color[0], color[1], color[2]),
+                stroke,
+            );
+            if response.clicked() {
+                self.edit_selected(|shape| shape.color = i);
+            }
+        }
+    }
+
+    fn clamp_selection(&mut self) {
+        self.selected_shape = self
+            .selected_shape
+            .min(self.project.shapes.len().saturating_sub(1));
+        self.selection.clear();
+        self.selected_auto_shape = None;
     }
 }
 
 impl App for Main {
-    fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
+    fn on_close_event(&mut self) -> bool {
+        if self.allow_close || !self.is_dirty() {
+            if let Some(play_test) = &mut self.play_test {
+                play_test.kill();
+            }
+            return true;
+        }
+        self.unsaved_prompt = Some(Discard::Close);
+        false
+    }
+
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.sync_song();
+
         if let Some(menu) = &mut self.options_menu {
             menu.ui(ctx);
             if menu.to_close {
@@ -50,31 +1080,171 @@ impl App for Main {
                 self.options = Options::load();
             }
         } else {
+            if ctx
+                .input_mut(|input| input.consume_key(Modifiers::COMMAND | Modifiers::SHIFT, Key::P))
+            {
+                self.command_palette = match self.command_palette {
+                    Some(_) => None,
+                    None => Some(CommandPalette::default()),
+                };
+            }
+            if let Some(palette) = &mut self.command_palette {
+                let command = palette.ui(ctx, &self.options.keymap);
+                if palette.to_close {
+                    self.command_palette = None;
+                }
+                if let Some(command) = command {
+                    self.run_command(command, ctx, frame);
+                }
+            }
+
+            if ctx.memory(|mem| mem.focus().is_none()) {
+                let (undo, redo, select_all) = ctx.input_mut(|input| {
+                    (
+                        input.consume_key(Modifiers::COMMAND, Key::Z),
+                        input.consume_key(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z),
+                        input.consume_key(Modifiers::COMMAND, Key::A),
+                    )
+                });
+                if undo {
+                    self.undo();
+                } else if redo {
+                    self.redo();
+                }
+                if select_all {
+                    self.select_all();
+                }
+
+                // The shortcuts also arrive as plain key presses, which would add shapes
+                let (mut copy, mut cut, duplicate) = ctx.input_mut(|input| {
+                    input.consume_key(Modifiers::COMMAND, Key::V);
+                    (
+                        input.consume_key(Modifiers::COMMAND, Key::C),
+                        input.consume_key(Modifiers::COMMAND, Key::X),
+                        input.consume_key(Modifiers::COMMAND, Key::D),
+                    )
+                });
+                let mut paste = None;
+                for event in ctx.input(|input| input.events.clone()) {
+                    match event {
+                        Event::Copy => copy = true,
+                        Event::Cut => cut = true,
+                        Event::Paste(text) => paste = Some(text),
+                        _ => {}
+                    }
+                }
+                if cut {
+                    self.cut_selected(ctx);
+                } else if copy {
+                    self.copy_selected(ctx);
+                }
+                if let Some(text) = paste {
+                    self.paste(&text);
+                }
+                if duplicate {
+                    self.duplicate_selected();
+                }
+            }
+            let (save, save_as) = ctx.input_mut(|input| {
+                (
+                    input.consume_key(Modifiers::COMMAND, Key::S),
+                    input.consume_key(Modifiers::COMMAND | Modifiers::SHIFT, Key::S),
+                )
+            });
+            if save {
+                self.save_file();
+            } else if save_as {
+                self.save_file_as();
+            }
+
+            if self.export.as_ref().is_some_and(JoinHandle::is_finished) {
+                let result = self.export.take().unwrap().join();
+                if let Err(err) = result.unwrap_or_else(|_| Err("Export failed".to_owned())) {
+                    self.error = Some(err);
+                }
+            }
+            if self
+                .tempo_analysis
+                .as_ref()
+                .is_some_and(JoinHandle::is_finished)
+            {
+                self.tempo_estimate = self.tempo_analysis.take().unwrap().join().ok().flatten();
+                if self.tempo_estimate.is_none() {
+                    self.error = Some("Couldn't find a steady beat in the song".into());
+                }
+            }
+
+            // Checked once for both the problem count and the problems panel
+            let diagnostics = validate(&self.project);
+
             egui::SidePanel::left("control_panel").show(ctx, |ui| {
                 ScrollArea::vertical().show(ui, |ui| {
                     ui.heading("Manifest");
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(self.history.can_undo(&self.project), Button::new("Undo"))
+                            .clicked()
+                        {
+                            self.undo();
+                        }
+                        if ui
+                            .add_enabled(self.history.can_redo(), Button::new("Redo"))
+                            .clicked()
+                        {
+                            self.redo();
+                        }
+                    });
                     if ui.button("New file").clicked() {
-                        self.project = Project::default();
+                        self.discard_project(Discard::NewFile, frame);
                     }
                     if ui.button("Load file").clicked() {
-                        if let Some(path) =
-                            FileDialog::new().add_filter("json", &["json"]).pick_file()
-                        {
-                            let file = read_to_string(path).unwrap();
-                            self.project = Project::from_json(json::parse(&file).unwrap()).unwrap();
-                        }
+                        self.discard_project(Discard::LoadFile, frame);
+                    }
+                    if ui.button("Open folder").clicked() {
+                        self.open_folder(frame);
                     }
                     if ui.button("Save file").clicked() {
-                        if let Some(mut path) =
-                            FileDialog::new().add_filter("json", &["json"]).save_file()
+                        self.save_file();
+                    }
+                    if ui.button("Save as").clicked() {
+                        self.save_file_as();
+                    }
+                    ui.horizontal(|ui| {
+                        let idle = self.export.is_none();
+                        if ui
+                            .add_enabled(idle, Button::new("Export image"))
+                            .on_hover_text("Save a PNG of the level at the selected step")
+                            .clicked()
                         {
-                            if path.extension().is_none() {
-                                path.set_extension("json");
-                            }
-                            let json = json::stringify_pretty(self.project.as_json(), 4);
-                            let mut file = File::create(path).unwrap();
-                            write!(file, "{json}").unwrap();
+                            self.export(false, ctx);
+                        }
+                        if ui
+                            .add_enabled(idle, Button::new("Export animation"))
+                            .on_hover_text("Save a GIF of the whole level at its tempo")
+                            .clicked()
+                        {
+                            self.export(true, ctx);
+                        }
+                        if !idle {
+                            ui.spinner();
                         }
+                    });
+                    if ui
+                        .add_enabled(
+                            !self.options.executable_path.is_empty(),
+                            Button::new("Play test"),
+                        )
+                        .on_disabled_hover_text("Set the game executable in Options first")
+                        .clicked()
+                    {
+                        self.start_play_test(ctx);
+                    }
+                    let problems = diagnostics.len();
+                    if ui
+                        .selectable_label(self.show_problems, format!("Problems ({problems})"))
+                        .clicked()
+                    {
+                        self.show_problems = !self.show_problems;
                     }
 
                     macro_rules! text_field {
@@ -105,18 +1275,133 @@ impl App for Main {
                         "Song author:" => song_author,
                     );
 
+                    ui.label("Song:");
+                    ui.horizontal(|ui| {
+                        match self.playback.song() {
+                            Some(song) => ui.label(format!(
+                                "{} ({}:{:02})",
+                                song.path.file_name().unwrap_or_default().to_string_lossy(),
+                                song.duration() as u32 / 60,
+                                song.duration() as u32 % 60,
+                            )),
+                            None => ui.weak("None"),
+                        };
+                        if ui.button("Attach").clicked() {
+                            self.pick_song();
+                        }
+                        if self.project.song.is_some() && ui.button("Detach").clicked() {
+                            self.project.song = None;
+                        }
+                    });
+
                     number_field!("BPM:" => bpm: NonZeroU16);
+                    ui.horizontal(|ui| {
+                        if self.tempo_analysis.is_some() {
+                            ui.spinner();
+                        } else if ui
+                            .add_enabled(self.playback.song().is_some(), Button::new("Detect"))
+                            .on_hover_text("Estimate the BPM and offset from the song")
+                            .clicked()
+                        {
+                            self.analyse_tempo(ui.ctx());
+                        }
+                        if ui
+                            .button("Tap")
+                            .on_hover_text("Tap along to the beat")
+                            .clicked()
+                        {
+                            self.tap_tempo.tap();
+                        }
+                        if let Some(bpm) = self
+                            .tap_tempo
+                            .bpm()
+                            .and_then(|bpm| NonZeroU16::new(bpm.round() as u16))
+                        {
+                            if ui.button(format!("Use {bpm}")).clicked() {
+                                self.project.header.bpm = bpm;
+                            }
+                        }
+                    });
+                    if let Some(estimate) = self.tempo_estimate {
+                        let offset = estimate.offset(&self.project.header);
+                        ui.label(format!(
+                            "Detected {:.1} BPM with the first beat at {:.2}s ({offset} beats)",
+                            estimate.bpm, estimate.first_beat,
+                        ));
+                        ui.horizontal(|ui| {
+                            if ui.button("Apply").clicked() {
+                                let header = &mut self.project.header;
+                                if let Some(bpm) = NonZeroU16::new(estimate.bpm.round() as u16) {
+                                    header.bpm = bpm;
+                                }
+                                if let Some(offset) = NonZeroU16::new(offset) {
+                                    header.offset = offset;
+                                    header.manual_offset = true;
+                                }
+                                self.tempo_estimate = None;
+                            }
+                            if ui.button("Dismiss").clicked() {
+                                self.tempo_estimate = None;
+                            }
+                        });
+                    }
+                    number_field!(
+                        "Beats per bar:" => time_signature_top: NonZeroU8,
+                        "Beat value:" => time_signature_bottom: NonZeroU8,
+                    );
                     ui.checkbox(&mut self.project.header.manual_offset, "Manual offset");
                     if self.project.header.manual_offset {
                         number_field!("Offset:" => offset: NonZeroU16);
                     }
 
+                    let header = &mut self.project.header;
+                    let swatch = |color: [u8; 3]| {
+                        RichText::new("⏹").color(Color32::from_rgb(color[0], color[1], color[2]))
+                    };
+                    ui.label("Background:");
+                    ComboBox::from_id_source("bg_color")
+                        .selected_text(format!("Color {}", header.bg_color))
+                        .show_ui(ui, |ui| {
+                            for (i, color) in (0..).zip(header.color_table) {
+                                ui.horizontal(|ui| {
+                                    ui.label(swatch(color));
+                                    ui.selectable_value(
+                                        &mut header.bg_color,
+                                        i,
+                                        format!("Color {i}"),
+                                    );
+                                });
+                            }
+                        });
+                    ui.label("Background effect:");
+                    ComboBox::from_id_source("background_effect")
+                        .selected_text(&header.background_effect)
+                        .show_ui(ui, |ui| {
+                            // Keep offering an effect the level came with
+                            // even if it isn't one we know
+                            let loaded = &self.saved.header.background_effect;
+                            let unknown = Some(loaded.as_str())
+                                .filter(|loaded| !BACKGROUND_EFFECTS.contains(loaded));
+                            for effect in BACKGROUND_EFFECTS.into_iter().chain(unknown) {
+                                ui.selectable_value(
+                                    &mut header.background_effect,
+                                    effect.to_owned(),
+                                    effect,
+                                );
+                            }
+                        });
+
                     ui.collapsing("Color table", |ui| {
-                        if let Some(current_shape) =
-                            self.project.shapes.get_mut(self.selected_shape)
+                        if let Some(color) = self
+                            .project
+                            .shapes
+                            .get(self.selected_shape)
+                            .map(|shape| shape.color)
                         {
                             for i in 0..16 {
-                                ui.radio_value(&mut current_shape.color, i, format!("Color {i}"));
+                                if ui.radio(color == i, format!("Color {i}")).clicked() {
+                                    self.edit_selected(|shape| shape.color = i);
+                                }
                                 ui.color_edit_button_srgb(&mut self.project.header.color_table[i]);
                             }
                         }
@@ -129,168 +1414,727 @@ impl App for Main {
                     ui.small(BUILD_VERSION);
                 });
             });
+            let mut close_pack = false;
+            if let Some(pack) = &mut self.level_pack {
+                let mut open = None;
+                egui::SidePanel::left("levels").show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.strong("Levels");
+                        if ui.small_button("⟳").on_hover_text("Refresh").clicked() {
+                            if let Err(err) = pack.refresh() {
+                                self.error = Some(format!(
+                                    "Could not read {}:\n{err}",
+                                    pack.directory.display()
+                                ));
+                            }
+                        }
+                        close_pack = ui.small_button("Close").clicked();
+                    });
+                    ui.weak(pack.directory.display().to_string());
+                    ui.separator();
+
+                    ScrollArea::vertical()
+                        .max_height(ui.available_height() - 60.0)
+                        .show(ui, |ui| {
+                            if pack.levels.is_empty() {
+                                ui.weak("No levels in this folder");
+                            }
+                            for level in &pack.levels {
+                                let current = self.path.as_ref() == Some(&level.path);
+                                let label = ui.selectable_label(current, &level.name);
+                                let label = match level.has_song {
+                                    true => label,
+                                    false => label.on_hover_text("No song"),
+                                };
+                                if label.clicked() && !current {
+                                    open = Some(level.path.clone());
+                                }
+                            }
+                        });
+
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        let field = ui.add(
+                            egui::TextEdit::singleline(&mut self.new_level_name)
+                                .id(egui::Id::new(NEW_LEVEL_FIELD))
+                                .hint_text("New level")
+                                .desired_width(100.0),
+                        );
+                        let entered =
+                            field.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+                        let named = !self.new_level_name.trim().is_empty();
+                        if (ui.add_enabled(named, Button::new("Create")).clicked() || entered)
+                            && named
+                        {
+                            match pack.create_level(&self.new_level_name) {
+                                Ok(path) => {
+                                    self.new_level_name.clear();
+                                    open = Some(path);
+                                }
+                                Err(err) => {
+                                    self.error = Some(format!(
+                                        "Could not create {}:\n{err}",
+                                        self.new_level_name
+                                    ));
+                                }
+                            }
+                        }
+                    });
+                });
+                if let Some(path) = open {
+                    self.discard_project(Discard::Open(path), frame);
+                }
+            }
+            if close_pack {
+                self.level_pack = None;
+            }
+
             egui::SidePanel::right("shapes").show(ctx, |ui| {
-                if ui.button("Remove shape").clicked() && !self.project.shapes.is_empty() {
-                    self.project.shapes.remove(self.selected_shape);
-                    self.selected_shape = self.selected_shape.saturating_sub(1);
+                let selected = self.selected_shapes();
+                if let Some(first) = selected.first().map(|i| &self.project.shapes[*i]) {
+                    ui.strong(match selected.len() {
+                        1 => format!("Shape {}", selected[0] + 1),
+                        count => format!("{count} shapes"),
+                    });
+                    let same_type = selected
+                        .iter()
+                        .all(|i| self.project.shapes[*i].ty == first.ty)
+                        .then_some(first.ty);
+                    let same_color = selected
+                        .iter()
+                        .all(|i| self.project.shapes[*i].color == first.color)
+                        .then_some(first.color);
+                    ui.horizontal(|ui| {
+                        for ty in ShapeType::ALL {
+                            if ui
+                                .selectable_label(same_type == Some(ty), ty.name())
+                                .clicked()
+                            {
+                                self.edit_selected(|shape| shape.ty = ty);
+                            }
+                        }
+                    });
+                    ComboBox::from_id_source("shape_color")
+                        .selected_text(
+                            same_color.map_or("Mixed colors".to_owned(), |color| {
+                                format!("Color {color}")
+                            }),
+                        )
+                        .show_ui(ui, |ui| {
+                            for i in 0..self.project.header.color_table.len() {
+                                let color = self.project.header.color_table[i];
+                                ui.horizontal(|ui| {
+                                    ui.label(
+                                        RichText::new("⏹")
+                                            .color(Color32::from_rgb(color[0], color[1], color[2])),
+                                    );
+                                    if ui
+                                        .selectable_label(
+                                            same_color == Some(i),
+                                            format!("Color {i}"),
+                                        )
+                                        .clicked()
+                                    {
+                                        self.edit_selected(|shape| shape.color = i);
+                                    }
+                                });
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        for (label, command) in [
+                            ("⏫", Command::MoveToStart),
+                            ("⏶", Command::MoveEarlier),
+                            ("⏷", Command::MoveLater),
+                            ("⏬", Command::MoveToEnd),
+                        ] {
+                            if ui.button(label).on_hover_text(command.name()).clicked() {
+                                self.run_command(command, ctx, frame);
+                            }
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("Duplicate").on_hover_text("Ctrl+D").clicked() {
+                            self.duplicate_selected();
+                        }
+                        let remove = match selected.len() {
+                            1 => "Remove shape",
+                            _ => "Remove shapes",
+                        };
+                        if ui.button(remove).clicked() {
+                            self.remove_selected();
+                        }
+                    });
+                }
+                let (mut add_auto_shape, mut remove_auto_shape) = (None, false);
+                if let Some(shape) = self.project.shapes.get_mut(self.selected_shape) {
+                    ui.collapsing("Auto shapes", |ui| {
+                        ui.horizontal(|ui| {
+                            for ty in ShapeType::ALL {
+                                if ui.button(format!("Add {}", ty.name())).clicked() {
+                                    add_auto_shape = Some(ty);
+                                }
+                            }
+                        });
+                        for (i, auto_shape) in shape.auto_shapes.iter().enumerate() {
+                            if ui
+                                .selectable_label(
+                                    self.selected_auto_shape == Some(i),
+                                    format!("{} {}", auto_shape.ty.name(), i + 1),
+                                )
+                                .clicked()
+                            {
+                                self.selected_auto_shape = Some(i);
+                            }
+                        }
+                        if let Some(i) = self
+                            .selected_auto_shape
+                            .filter(|i| *i < shape.auto_shapes.len())
+                        {
+                            ui.separator();
+                            let auto_shape = &mut shape.auto_shapes[i];
+                            ui.horizontal(|ui| {
+                                for ty in ShapeType::ALL {
+                                    ui.radio_value(&mut auto_shape.ty, ty, ty.name());
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("X:");
+                                ui.add(DragValue::new(&mut auto_shape.pos.x).clamp_range(0..=14));
+                                ui.label("Y:");
+                                ui.add(DragValue::new(&mut auto_shape.pos.y).clamp_range(0..=14));
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Size:");
+                                ui.add(DragValue::new(&mut auto_shape.size).clamp_range(0..=7));
+                                ui.label("Color:");
+                                ui.add(DragValue::new(&mut auto_shape.color).clamp_range(0..=15));
+                            });
+                            remove_auto_shape = ui.button("Remove auto shape").clicked();
+                        }
+                    });
+                }
+                if let Some(ty) = add_auto_shape {
+                    self.add_auto_shape(ty);
+                }
+                if remove_auto_shape {
+                    self.remove_auto_shape();
                 }
                 ScrollArea::vertical().show(ui, |ui| {
                     let width = ui.available_size_before_wrap().x;
+                    let selected = self.selected_shapes();
+                    let mut thumbnails = Vec::with_capacity(self.project.shapes.len());
                     for i in 0..self.project.shapes.len() {
-                        if self
-                            .project
-                            .draw(ui, Some(Vec2::splat(width)), i + 1)
-                            .clicked()
-                        {
-                            self.selected_shape = i;
+                        let response = self.project.draw(ui, Some(Vec2::splat(width)), i + 1);
+                        if selected.contains(&i) {
+                            ui.painter().rect_stroke(
+                                response.rect,
+                                0.0,
+                                ui.visuals().selection.stroke,
+                            );
                         }
+                        let (travel, modifiers) = ui.input(|input| {
+                            let pointer = &input.pointer;
+                            let travel = pointer.interact_pos().zip(pointer.press_origin());
+                            (
+                                travel
+                                    .filter(|_| response.dragged())
+                                    .map(|(pos, origin)| pos - origin),
+                                input.modifiers,
+                            )
+                        });
+                        self.thumbnail_input(i, response.clicked(), travel, modifiers);
+                        thumbnails.push(response.rect);
+                    }
+
+                    let pointer = ui.input(|input| input.pointer.interact_pos());
+                    if let (true, Some(pointer)) = (self.reordering, pointer) {
+                        ctx.set_cursor_icon(egui::CursorIcon::Grabbing);
+                        // The gap between thumbnails closest to the pointer
+                        let gap = thumbnails
+                            .iter()
+                            .filter(|rect| rect.center().y < pointer.y)
+                            .count();
+                        let y = match thumbnails.get(gap) {
+                            Some(rect) => rect.top() - ui.spacing().item_spacing.y / 2.0,
+                            None => thumbnails.last().map_or(0.0, |rect| rect.bottom()),
+                        };
+                        ui.painter().hline(
+                            ui.max_rect().x_range(),
+                            y,
+                            ui.visuals().selection.stroke,
+                        );
+                        if !ui.input(|input| input.pointer.any_down()) {
+                            self.reordering = false;
+                            self.drop_selected(gap);
+                        }
+                    } else {
+                        self.reordering = false;
                     }
                 });
             });
             egui::TopBottomPanel::bottom("steps").show(ctx, |ui| {
-                ScrollArea::horizontal().show(ui, |ui| {
-                    if let Some(shape) = self.project.shapes.get_mut(self.selected_shape) {
-                        ui.horizontal(|ui| match &shape.moves {
-                            Some(moves) => {}
-                            None => {
-                                if ui.button("Add Sequence").clicked() {
-                                    shape.moves = Some(vec![]);
+                let (mut add_sequence, mut remove_sequence) = (false, false);
+                if let Some(shape) = self.project.shapes.get_mut(self.selected_shape) {
+                    match &mut shape.moves {
+                        Some(moves) => {
+                            self.selected_step =
+                                self.selected_step.min(moves.len().saturating_sub(1));
+                            ui.horizontal(|ui| {
+                                for step in Move::ALL {
+                                    if ui
+                                        .button(step.symbol())
+                                        .on_hover_text(step.name())
+                                        .clicked()
+                                    {
+                                        match moves.get_mut(self.selected_step) {
+                                            Some(selected) => *selected = step,
+                                            None => moves.push(step),
+                                        }
+                                    }
                                 }
-                            }
-                        });
+                                ui.separator();
+                                if ui.button("Insert").clicked() {
+                                    let index = (self.selected_step + 1).min(moves.len());
+                                    moves.insert(index, Move::Rest);
+                                    self.selected_step = index;
+                                }
+                                if ui.button("Delete").clicked() && self.selected_step < moves.len()
+                                {
+                                    moves.remove(self.selected_step);
+                                    self.selected_step = self.selected_step.saturating_sub(1);
+                                }
+                                if ui.button("⏴").on_hover_text("Move step earlier").clicked()
+                                    && self.selected_step > 0
+                                    && self.selected_step < moves.len()
+                                {
+                                    moves.swap(self.selected_step - 1, self.selected_step);
+                                    self.selected_step -= 1;
+                                }
+                                if ui.button("⏵").on_hover_text("Move step later").clicked()
+                                    && self.selected_step + 1 < moves.len()
+                                {
+                                    moves.swap(self.selected_step, self.selected_step + 1);
+                                    self.selected_step += 1;
+                                }
+                                if ui.button("Clear").clicked() {
+                                    moves.clear();
+                                    self.selected_step = 0;
+                                }
+                                remove_sequence = ui.button("Remove Sequence").clicked();
+                            });
+                            ScrollArea::horizontal().show(ui, |ui| {
+                                ui.horizontal(|ui| {
+                                    for (i, step) in moves.iter().enumerate() {
+                                        if ui
+                                            .selectable_label(
+                                                i == self.selected_step,
+                                                step.symbol(),
+                                            )
+                                            .on_hover_text(format!(
+                                                "Step {}: {}",
+                                                i + 1,
+                                                step.name()
+                                            ))
+                                            .clicked()
+                                        {
+                                            self.selected_step = i;
+                                        }
+                                    }
+                                });
+                            });
+                        }
+                        None => add_sequence = ui.button("Add Sequence").clicked(),
                     }
-                })
+                }
+                if add_sequence {
+                    self.add_sequence();
+                }
+                if remove_sequence {
+                    self.remove_sequence();
+                }
             });
-            egui::CentralPanel::default().show(ctx, |ui| {
-                if ctx.memory(|mem| mem.focus().is_none()) {
-                    ui.input(|input| {
-                        if let Some(shape) = self.project.shapes.get_mut(self.selected_shape) {
-                            if input.key_pressed(Key::ArrowUp) {
-                                shape.pos.y = (shape.pos.y - 1.0).max(0.0);
+            if self.show_problems {
+                egui::TopBottomPanel::bottom("problems")
+                    .resizable(true)
+                    .show(ctx, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.strong("Problems");
+                            if ui.button("Close").clicked() {
+                                self.show_problems = false;
                             }
-                            if input.key_pressed(Key::ArrowDown) {
-                                shape.pos.y = (shape.pos.y + 1.0).min(14.0);
-                            }
-                            if input.key_pressed(Key::ArrowLeft) {
-                                shape.pos.x = (shape.pos.x - 1.0).max(0.0);
-                            }
-                            if input.key_pressed(Key::ArrowRight) {
-                                shape.pos.x = (shape.pos.x + 1.0).min(14.0);
-                            }
-                            if input.key_pressed(Key::A) {
-                                shape.size = (shape.size - 1.0).max(0.0);
-                            }
-                            if input.key_pressed(Key::S) {
-                                shape.size = (shape.size + 1.0).min(7.0);
-                            }
-                            if input.key_pressed(Key::Num0) {
-                                shape.color = 0;
-                            }
-                            if input.key_pressed(Key::Num1) {
-                                shape.color = 1;
-                            }
-                            if input.key_pressed(Key::Num2) {
-                                shape.color = 2;
-                            }
-                            if input.key_pressed(Key::Num3) {
-                                shape.color = 3;
-                            }
-                            if input.key_pressed(Key::Num4) {
-                                shape.color = 4;
-                            }
-                            if input.key_pressed(Key::Num5) {
-                                shape.color = 5;
-                            }
-                            if input.key_pressed(Key::Num6) {
-                                shape.color = 6;
-                            }
-                            if input.key_pressed(Key::Num7) {
-                                shape.color = 7;
-                            }
-                            if input.key_pressed(Key::Num8) {
-                                shape.color = 8;
-                            }
-                            if input.key_pressed(Key::Num9) {
-                                shape.color = 9;
-                            }
-                            if input.key_pressed(Key::Enter) {
-                                if input.modifiers.shift {
-                                    if self.selected_shape == 0 {
-                                        self.selected_shape = self.project.shapes.len() - 1;
-                                    } else {
-                                        self.selected_shape -= 1;
+                        });
+                        ScrollArea::vertical()
+                            .auto_shrink([false, true])
+                            .show(ui, |ui| {
+                                if diagnostics.is_empty() {
+                                    ui.weak("No problems found");
+                                }
+                                for diagnostic in &diagnostics {
+                                    ui.horizontal(|ui| {
+                                        match diagnostic.severity {
+                                            Severity::Error => ui.colored_label(
+                                                ui.visuals().error_fg_color,
+                                                "Error",
+                                            ),
+                                            Severity::Warning => ui.colored_label(
+                                                ui.visuals().warn_fg_color,
+                                                "Warning",
+                                            ),
+                                        };
+                                        let text = match diagnostic.shape {
+                                            Some(shape) => {
+                                                format!(
+                                                    "Shape {}: {}",
+                                                    shape + 1,
+                                                    diagnostic.message
+                                                )
+                                            }
+                                            None => diagnostic.message.clone(),
+                                        };
+                                        let selected =
+                                            diagnostic.shape == Some(self.selected_shape);
+                                        if ui.selectable_label(selected, text).clicked() {
+                                            if let Some(shape) = diagnostic.shape {
+                                                self.select_shape(shape, Modifiers::NONE);
+                                            }
+                                        }
+                                    });
+                                }
+                            });
+                    });
+            }
+            if self.show_play_log {
+                egui::TopBottomPanel::bottom("play_log")
+                    .resizable(true)
+                    .show(ctx, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.strong("Play test");
+                            if let Some(play_test) = &mut self.play_test {
+                                match play_test.poll() {
+                                    Some(status) => {
+                                        ui.label(format!("Exited ({status})"));
+                                    }
+                                    None => {
+                                        ui.label("Running");
+                                        if ui.button("Stop").clicked() {
+                                            play_test.kill();
+                                        }
                                     }
-                                } else {
-                                    self.selected_shape =
-                                        (self.selected_shape + 1) % self.project.shapes.len();
                                 }
                             }
+                            if ui.button("Close").clicked() {
+                                self.show_play_log = false;
+                            }
+                        });
+                        if let Some(play_test) = &self.play_test {
+                            ScrollArea::vertical()
+                                .stick_to_bottom(true)
+                                .auto_shrink([false, true])
+                                .show(ui, |ui| {
+                                    for line in play_test.log.lock().unwrap().iter() {
+                                        let text = RichText::new(&line.text).monospace();
+                                        ui.label(match line.stderr {
+                                            true => text.color(ui.visuals().warn_fg_color),
+                                            false => text,
+                                        });
+                                    }
+                                });
+                        }
+                    });
+            }
+            self.playback.update(&self.project);
+            if self.playback.state == State::Playing {
+                ctx.request_repaint();
+            }
+            egui::CentralPanel::default().show(ctx, |ui| {
+                self.playback.ui(ui, &self.project);
+                if ctx.memory(|mem| mem.focus().is_none()) {
+                    let pressed = ui.input(|input| {
+                        Action::ALL
+                            .iter()
+                            .copied()
+                            .filter(|action| self.options.keymap.pressed(*action, input))
+                            .collect::<Vec<_>>()
+                    });
+                    for action in pressed {
+                        self.run_action(action);
+                    }
+                }
+                if self.playback.is_active() {
+                    self.project
+                        .at_beat(self.playback.beat)
+                        .draw(ui, None, usize::MAX);
+                } else {
+                    let canvas = ui.available_rect_before_wrap();
+                    self.palette_strip(ui, canvas);
+                    let visible = self.visible_shapes();
+                    let response = self.project.draw(ui, Some(canvas.size()), visible);
+                    self.canvas_input(ui, &response);
+                }
+            });
+
+            // Drags and text entry are only recorded once they finish, so each
+            // becomes a single undo step.
+            let continuing = ctx.input(|input| input.pointer.any_down())
+                || ctx.memory(|mem| mem.focus().is_some());
+            self.history.record(&self.project, continuing);
+        }
+
+        if let Some(discard) = self.unsaved_prompt.clone() {
+            let mut choice = None;
+            egui::Window::new("Unsaved changes")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ctx, |ui| {
+                    ui.label("Save changes to this level first?");
+                    ui.horizontal(|ui| {
+                        if ui.button("Save").clicked() {
+                            choice = Some(true);
+                        }
+                        if ui.button("Discard").clicked() {
+                            choice = Some(false);
                         }
-                        let to_add = if input.key_pressed(Key::Z) {
-                            Some(Shape {
-                                ty: shape::ShapeType::Circle,
-                                color: self
-                                    .project
-                                    .shapes
-                                    .get(self.selected_shape)
-                                    .map(|shape| shape.color)
-                                    .unwrap_or_default(),
-                                ..Default::default()
-                            })
-                        } else if input.key_pressed(Key::X) {
-                            Some(Shape {
-                                ty: shape::ShapeType::Square,
-                                color: self
-                                    .project
-                                    .shapes
-                                    .get(self.selected_shape)
-                                    .map(|shape| shape.color)
-                                    .unwrap_or_default(),
-                                ..Default::default()
-                            })
-                        } else if input.key_pressed(Key::C) {
-                            Some(Shape {
-                                ty: shape::ShapeType::Triangle,
-                                color: self
-                                    .project
-                                    .shapes
-                                    .get(self.selected_shape)
-                                    .map(|shape| shape.color)
-                                    .unwrap_or_default(),
-                                ..Default::default()
-                            })
-                        } else {
-                            None
+                        if ui.button("Cancel").clicked() {
+                            self.unsaved_prompt = None;
+                        }
+                    });
+                });
+            if let Some(save) = choice {
+                self.unsaved_prompt = None;
+                if !save || self.save_file() {
+                    self.run_discard(discard, frame);
+                } else if let Some(InvalidSave {
+                    save: HeldSave::File(_, then),
+                    ..
+                }) = &mut self.invalid_save
+                {
+                    *then = Some(discard);
+                }
+            }
+        }
+
+        if let Some(InvalidSave { errors, save }) = self.invalid_save.clone() {
+            let mut choice = None;
+            egui::Window::new("Level has errors")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "This level has {errors} error{} and may not play in Open Manifold.",
+                        if errors == 1 { "" } else { "s" },
+                    ));
+                    ui.horizontal(|ui| {
+                        let anyway = match &save {
+                            HeldSave::File(..) => "Save anyway",
+                            HeldSave::PlayTest => "Play anyway",
                         };
-                        if let Some(to_add) = to_add {
-                            if self.project.shapes.len() <= self.selected_shape {
-                                self.selected_shape = self.project.shapes.len();
-                                self.project.shapes.push(to_add);
-                            } else {
-                                self.selected_shape += 1;
-                                self.project.shapes.insert(self.selected_shape, to_add);
+                        if ui.button(anyway).clicked() {
+                            choice = Some(true);
+                        }
+                        if ui.button("Show problems").clicked() {
+                            choice = Some(false);
+                            self.show_problems = true;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            choice = Some(false);
+                        }
+                    });
+                });
+            if let Some(go_ahead) = choice {
+                self.invalid_save = None;
+                if go_ahead {
+                    match save {
+                        HeldSave::File(path, then) => {
+                            if self.save_unchecked(path) {
+                                if let Some(discard) = then {
+                                    self.run_discard(discard, frame);
+                                }
                             }
                         }
+                        HeldSave::PlayTest => self.launch_play_test(ctx),
+                    }
+                }
+            }
+        }
+
+        let title = format!(
+            "{}{} - Manifest",
+            if self.is_dirty() { "*" } else { "" },
+            self.path
+                .as_ref()
+                .and_then(|path| path.file_name())
+                .map_or("Untitled".into(), |name| name.to_string_lossy()),
+        );
+        if title != self.title {
+            frame.set_window_title(&title);
+            self.title = title;
+        }
+
+        if let Some(copy) = self.song_copy.clone() {
+            let mut choice = None;
+            egui::Window::new("Replace song?")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "{} already exists. Replace it with {}?",
+                        copy.destination.display(),
+                        copy.song.display(),
+                    ));
+                    ui.horizontal(|ui| {
+                        if ui.button("Replace").clicked() {
+                            choice = Some(true);
+                        }
+                        if ui.button("Keep existing").clicked() {
+                            choice = Some(false);
+                        }
                     });
+                });
+            if let Some(replace) = choice {
+                self.song_copy = None;
+                if replace {
+                    self.copy_song(&copy.song, &copy.level, copy.attach);
+                } else if !copy.attach {
+                    self.adopt_song(Song::find_beside(&copy.level));
                 }
-                self.project.draw(ui, None, self.selected_shape + 1)
-            });
+            }
+        }
+
+        if message_window(ctx, "Warning", self.warning.as_deref()) {
+            self.warning = None;
+        }
+        if message_window(ctx, "Error", self.error.as_deref()) {
+            self.error = None;
         }
     }
 }
 
+/// Shows `message` if there is one, returning whether it was dismissed.
+fn message_window(ctx: &egui::Context, title: &str, message: Option<&str>) -> bool {
+    let Some(message) = message else {
+        return false;
+    };
+    let mut dismissed = false;
+    egui::Window::new(title)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(message);
+            dismissed = ui.button("OK").clicked();
+        });
+    dismissed
+}
+
+#[cfg(test)]
+#[test]
+fn thumbnail_reordering() {
+    let mut main = Main {
+        project: Project {
+            shapes: (0..5)
+                .map(|color| Shape {
+                    color,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        },
+        playback: Playback::with_backend(Box::<audio::NullBackend>::default()),
+        ..Default::default()
+    };
+    // Each click is a press that reports a drag, then a release that reports the click
+    let click = |main: &mut Main, i, modifiers| {
+        main.thumbnail_input(i, false, Some(Vec2::ZERO), modifiers);
+        main.thumbnail_input(i, true, None, modifiers);
+    };
+
+    click(&mut main, 2, Modifiers::COMMAND);
+    assert_eq!(main.selected_shapes(), [0, 2]);
+    click(&mut main, 2, Modifiers::COMMAND);
+    assert_eq!(main.selected_shapes(), [0]);
+    assert!(!main.reordering);
+
+    click(&mut main, 3, Modifiers::COMMAND);
+    main.thumbnail_input(3, false, Some(Vec2::new(0.0, 20.0)), Modifiers::NONE);
+    assert!(main.reordering);
+    assert_eq!(main.selected_shapes(), [0, 3]);
+
+    // Dropped below the last shape
+    main.drop_selected(5);
+    let colors = main.project.shapes.iter().map(|shape| shape.color);
+    assert_eq!(colors.collect::<Vec<_>>(), [1, 2, 4, 0, 3]);
+    assert_eq!(main.selected_shapes(), [3, 4]);
+    main.reordering = false;
+
+    // Dragging an unselected shape picks it up on its own
+    main.thumbnail_input(0, false, Some(Vec2::new(0.0, 20.0)), Modifiers::NONE);
+    assert!(main.reordering);
+    assert_eq!(main.selected_shapes(), [0]);
+    main.drop_selected(2);
+    let colors = main.project.shapes.iter().map(|shape| shape.color);
+    assert_eq!(colors.collect::<Vec<_>>(), [2, 1, 4, 0, 3]);
+}
+
+#[cfg(test)]
+#[test]
+fn keyboard_edits_keep_layout() {
+    let mut main = Main {
+        project: Project {
+            shapes: vec![
+                Shape {
+                    pos: Vec2::new(13.0, 5.0),
+                    size: 6.0,
+                    ..Default::default()
+                },
+                Shape {
+                    pos: Vec2::new(10.0, 5.0),
+                    size: 2.0,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        },
+        playback: Playback::with_backend(Box::<audio::NullBackend>::default()),
+        ..Default::default()
+    };
+    main.select_all();
+    for _ in 0..3 {
+        main.run_action(Action::MoveRight);
+        main.run_action(Action::Grow);
+    }
+    let shapes = main
+        .project
+        .shapes
+        .iter()
+        .map(|shape| (shape.pos.x, shape.size));
+    assert_eq!(shapes.collect::<Vec<_>>(), [(14.0, 7.0), (11.0, 3.0)]);
+
+    main.insert_shapes(vec![Shape::default()]);
+    assert!(main.selection.is_empty());
+    assert_eq!(main.selected_shapes(), [2]);
+}
+
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Some(code) = cli::run(&args) {
+        exit(code);
+    }
+
+    let file = args.first().map(PathBuf::from).map(|path| {
+        let project = Project::load(&path).unwrap_or_else(|err| {
+            eprintln!("Could not load {}: {err}", path.display());
+            exit(1);
+        });
+        (path, project)
+    });
+
     eframe::run_native(
         "Manifest",
         eframe::NativeOptions::default(),
-        Box::new(|cc| {
-            Box::new(Main::new(
-                cc,
-                std::env::args().nth(1).and_then(|a| {
-                    Project::from_json(json::parse(&read_to_string(a).unwrap()).unwrap())
-                }),
-            ))
-        }),
+        Box::new(|cc| Box::new(Main::new(cc, file))),
     )
     .unwrap();
 }
@@ -4,13 +4,19 @@ use std::{
     path::PathBuf,
 };
 
+use egui::{Button, Event, Key};
 use etcetera::{choose_app_strategy, AppStrategy, AppStrategyArgs};
+use rfd::FileDialog;
 use serde::{Deserialize, Serialize};
 
+use crate::keymap::{Action, Binding, Keymap};
+
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Options {
     pub dark_theme: bool,
     pub executable_path: String,
+    pub keymap: Keymap,
 }
 
 impl Options {
@@ -44,12 +50,15 @@ impl Default for Options {
         Self {
             dark_theme: true,
             executable_path: String::new(),
+            keymap: Keymap::default(),
         }
     }
 }
 
 pub struct OptionsMenu {
     options: Options,
+    /// The action waiting for a key to be pressed for it.
+    rebinding: Option<Action>,
 
     pub to_close: bool,
 }
@@ -58,11 +67,42 @@ impl OptionsMenu {
     pub fn new(options: Options) -> Self {
         Self {
             options,
+            rebinding: None,
             to_close: false,
         }
     }
 
     pub fn ui(&mut self, ctx: &egui::Context) {
+        if let Some(action) = self.rebinding {
+            let pressed = ctx.input_mut(|input| {
+                let pressed = input.events.iter().find_map(|event| match event {
+                    Event::Key {
+                        key,
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } => Some(Binding::new(*modifiers, *key)),
+                    _ => None,
+                });
+                if pressed.is_some() {
+                    input
+                        .events
+                        .retain(|event| !matches!(event, Event::Key { .. }));
+                }
+                pressed
+            });
+            match pressed {
+                Some(binding) if binding.key == Key::Escape && binding.modifiers.is_none() => {
+                    self.rebinding = None;
+                }
+                Some(binding) => {
+                    self.options.keymap.set(action, binding);
+                    self.rebinding = None;
+                }
+                None => {}
+            }
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("Options");
 
@@ -76,7 +116,67 @@ impl OptionsMenu {
                 });
             }
 
-            if ui.button("OK").clicked() {
+            ui.label("Open Manifold executable:");
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.options.executable_path);
+                if ui.button("Browse").clicked() {
+                    if let Some(path) = FileDialog::new().pick_file() {
+                        self.options.executable_path = path.display().to_string();
+                    }
+                }
+            });
+
+            let conflicting = self.options.keymap.has_conflicts();
+            ui.collapsing("Keyboard shortcuts", |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(ui.available_height() - 80.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("keymap").striped(true).show(ui, |ui| {
+                            for action in Action::ALL {
+                                ui.label(action.name());
+                                let text = match self.rebinding == Some(*action) {
+                                    true => "Press a key…".to_owned(),
+                                    false => self.options.keymap.get(*action).to_string(),
+                                };
+                                if ui
+                                    .button(text)
+                                    .on_hover_text(
+                                        "Click, then press the new shortcut, or Escape to cancel",
+                                    )
+                                    .clicked()
+                                {
+                                    self.rebinding = Some(*action);
+                                }
+                                let conflicts = self.options.keymap.conflicts(*action);
+                                if conflicts.is_empty() {
+                                    ui.label("");
+                                } else {
+                                    ui.colored_label(
+                                        ui.visuals().error_fg_color,
+                                        format!("Also used for {}", conflicts.join(", ")),
+                                    );
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+                if ui.button("Reset to defaults").clicked() {
+                    self.options.keymap = Keymap::default();
+                    self.rebinding = None;
+                }
+            });
+
+            if conflicting {
+                ui.colored_label(
+                    ui.visuals().error_fg_color,
+                    "Some keyboard shortcuts conflict",
+                );
+            }
+            if ui
+                .add_enabled(!conflicting, Button::new("OK"))
+                .on_disabled_hover_text("Some keyboard shortcuts conflict")
+                .clicked()
+            {
                 self.to_close = true;
                 self.options.save();
                 return;
@@ -0,0 +1,290 @@
+use egui::{Align, Key, Layout, Modifiers, ScrollArea, TextEdit};
+
+use crate::{
+    keymap::{Action, Binding, Keymap},
+    shape::ShapeType,
+};
+
+/// Everything the command palette can run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    NewFile,
+    LoadFile,
+    OpenFolder,
+    Save,
+    SaveAs,
+    ExportImage,
+    ExportAnimation,
+    Undo,
+    Redo,
+    SelectAll,
+    Copy,
+    Cut,
+    Duplicate,
+    MoveToStart,
+    MoveEarlier,
+    MoveLater,
+    MoveToEnd,
+    PlayPause,
+    Stop,
+    PlayTest,
+    Problems,
+    Options,
+    AttachSong,
+    DetachSong,
+    DetectTempo,
+    TapTempo,
+    NewLevel,
+    AddSequence,
+    RemoveSequence,
+    RemoveAutoShape,
+    /// Anything with a rebindable shortcut.
+    Action(Action),
+    SetShapeType(ShapeType),
+    AddAutoShape(ShapeType),
+}
+
+impl Command {
+    const GENERAL: [Self; 30] = [
+        Self::NewFile,
+        Self::LoadFile,
+        Self::OpenFolder,
+        Self::Save,
+        Self::SaveAs,
+        Self::ExportImage,
+        Self::ExportAnimation,
+        Self::Undo,
+        Self::Redo,
+        Self::SelectAll,
+        Self::Copy,
+        Self::Cut,
+        Self::Duplicate,
+        Self::MoveToStart,
+        Self::MoveEarlier,
+        Self::MoveLater,
+        Self::MoveToEnd,
+        Self::PlayPause,
+        Self::Stop,
+        Self::PlayTest,
+        Self::Problems,
+        Self::Options,
+        Self::AttachSong,
+        Self::DetachSong,
+        Self::DetectTempo,
+        Self::TapTempo,
+        Self::NewLevel,
+        Self::AddSequence,
+        Self::RemoveSequence,
+        Self::RemoveAutoShape,
+    ];
+
+    /// Every command, in the order they're listed before anything is typed.
+    pub fn all() -> impl Iterator<Item = Self> {
+        Self::GENERAL
+            .into_iter()
+            .chain(Action::ALL.iter().copied().map(Self::Action))
+            .chain(ShapeType::ALL.map(Self::SetShapeType))
+            .chain(ShapeType::ALL.map(Self::AddAutoShape))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::NewFile => "New file",
+            Self::LoadFile => "Load file",
+            Self::OpenFolder => "Open level folder",
+            Self::Save => "Save file",
+            Self::SaveAs => "Save as",
+            Self::ExportImage => "Export image",
+            Self::ExportAnimation => "Export animation",
+            Self::Undo => "Undo",
+            Self::Redo => "Redo",
+            Self::SelectAll => "Select all shapes",
+            Self::Copy => "Copy shapes",
+            Self::Cut => "Cut shapes",
+            Self::Duplicate => "Duplicate shapes",
+            Self::MoveToStart => "Move shapes to start",
+            Self::MoveEarlier => "Move shapes earlier",
+            Self::MoveLater => "Move shapes later",
+            Self::MoveToEnd => "Move shapes to end",
+            Self::PlayPause => "Play/pause preview",
+            Self::Stop => "Stop preview",
+            Self::PlayTest => "Play test in Open Manifold",
+            Self::Problems => "Show problems",
+            Self::Options => "Options",
+            Self::AttachSong => "Attach song",
+            Self::DetachSong => "Detach song",
+            Self::DetectTempo => "Detect tempo from song",
+            Self::TapTempo => "Tap tempo",
+            Self::NewLevel => "New level in pack",
+            Self::AddSequence => "Add sequence",
+            Self::RemoveSequence => "Remove sequence",
+            Self::RemoveAutoShape => "Remove auto shape",
+            Self::Action(action) => action.name(),
+            Self::SetShapeType(ShapeType::Circle) => "Make shapes circles",
+            Self::SetShapeType(ShapeType::Square) => "Make shapes squares",
+            Self::SetShapeType(ShapeType::Triangle) => "Make shapes triangles",
+            Self::AddAutoShape(ShapeType::Circle) => "Add auto circle",
+            Self::AddAutoShape(ShapeType::Square) => "Add auto square",
+            Self::AddAutoShape(ShapeType::Triangle) => "Add auto triangle",
+        }
+    }
+
+    /// The shortcut that does the same thing, if there is one.
+    pub fn shortcut(self, keymap: &Keymap) -> Option<Binding> {
+        let (modifiers, key) = match self {
+            Self::Action(action) => return Some(keymap.get(action)),
+            Self::Save => (Modifiers::COMMAND, Key::S),
+            Self::SaveAs => (Modifiers::COMMAND | Modifiers::SHIFT, Key::S),
+            Self::Undo => (Modifiers::COMMAND, Key::Z),
+            Self::Redo => (Modifiers::COMMAND | Modifiers::SHIFT, Key::Z),
+            Self::SelectAll => (Modifiers::COMMAND, Key::A),
+            Self::Copy => (Modifiers::COMMAND, Key::C),
+            Self::Cut => (Modifiers::COMMAND, Key::X),
+            Self::Duplicate => (Modifiers::COMMAND, Key::D),
+            Self::PlayPause => (Modifiers::NONE, Key::Space),
+            _ => return None,
+        };
+        Some(Binding::new(modifiers, key))
+    }
+}
+
+/// Scores how well `query` matches `text` as a subsequence ignoring case,
+/// favouring runs of letters and the starts of words, or `None` if it doesn't.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text = text
+        .chars()
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+    let mut score = 0;
+    let mut next = 0;
+    for c in query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+    {
+        let found = next + text[next..].iter().position(|t| *t == c)?;
+        let skipped = found - next;
+        score += if next > 0 && skipped == 0 {
+            4
+        } else if found == 0 || !text[found - 1].is_alphanumeric() {
+            3
+        } else {
+            1
+        };
+        score -= skipped.min(5) as i32;
+        next = found + 1;
+    }
+    Some(score)
+}
+
+/// A search box over every [`Command`].
+#[derive(Default)]
+pub struct CommandPalette {
+    query: String,
+    /// Index of the highlighted command among the matches.
+    selected: usize,
+
+    pub to_close: bool,
+}
+
+impl CommandPalette {
+    /// Commands matching the query, best first.
+    fn matches(&self) -> Vec<Command> {
+        let mut matches = Command::all()
+            .filter_map(|command| Some((fuzzy_score(&self.query, command.name())?, command)))
+            .collect::<Vec<_>>();
+        // Stable, so equally good matches stay in their usual order
+        matches.sort_by_key(|(score, _)| -score);
+        matches.into_iter().map(|(_, command)| command).collect()
+    }
+
+    /// Shows the palette, returning the command picked if there was one.
+    pub fn ui(&mut self, ctx: &egui::Context, keymap: &Keymap) -> Option<Command> {
+        let (up, down, enter, escape) = ctx.input_mut(|input| {
+            (
+                input.consume_key(Modifiers::NONE, Key::ArrowUp),
+                input.consume_key(Modifiers::NONE, Key::ArrowDown),
+                input.consume_key(Modifiers::NONE, Key::Enter),
+                input.consume_key(Modifiers::NONE, Key::Escape),
+            )
+        });
+        let matches = self.matches();
+        if down {
+            self.selected = (self.selected + 1).min(matches.len().saturating_sub(1));
+        }
+        if up {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        let mut picked = matches.get(self.selected).copied().filter(|_| enter);
+
+        egui::Window::new("Command palette")
+            .title_bar(false)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 40.0])
+            .fixed_size([360.0, 0.0])
+            .show(ctx, |ui| {
+                let search = ui.add(
+                    TextEdit::singleline(&mut self.query)
+                        .hint_text("Search commands")
+                        .desired_width(f32::INFINITY),
+                );
+                search.request_focus();
+                if search.changed() {
+                    self.selected = 0;
+                }
+
+                ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                    if matches.is_empty() {
+                        ui.weak("No matching commands");
+                    }
+                    for (i, command) in matches.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            let label = ui.selectable_label(i == self.selected, command.name());
+                            if i == self.selected && (up || down) {
+                                label.scroll_to_me(None);
+                            }
+                            if label.clicked() {
+                                picked = Some(*command);
+                            }
+                            if let Some(shortcut) = command.shortcut(keymap) {
+                                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                                    ui.weak(shortcut.to_string());
+                                });
+                            }
+                        });
+                    }
+                });
+            });
+
+        self.to_close = escape || picked.is_some();
+        picked
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn fuzzy_matching() {
+    assert_eq!(fuzzy_score("", "Undo"), Some(0));
+    assert_eq!(fuzzy_score("xyz", "Undo"), None);
+    assert_eq!(fuzzy_score("ODN", "Undo"), None);
+    assert!(fuzzy_score("sva", "Save as").is_some());
+
+    assert!(fuzzy_score("mv", "Move up") > fuzzy_score("mv", "Remove shapes"));
+    assert!(fuzzy_score("save", "Save as") > fuzzy_score("save", "Select all shapes"));
+    assert!(fuzzy_score("ex an", "Export animation") > fuzzy_score("ex an", "Export image"));
+
+    let palette = CommandPalette {
+        query: "add tri".to_owned(),
+        ..Default::default()
+    };
+    assert_eq!(
+        palette.matches().first(),
+        Some(&Command::Action(Action::AddTriangle))
+    );
+
+    let mut names = Command::all().map(Command::name).collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+    assert_eq!(names.len(), Command::all().count());
+}
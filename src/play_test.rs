@@ -0,0 +1,135 @@
+use std::{
+    io::{self, BufRead, BufReader, Read},
+    path::{self, Path},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+/// A line of output from the game.
+pub struct LogLine {
+    pub stderr: bool,
+    pub text: String,
+}
+
+/// A running instance of Open Manifold playing a level, with its output captured.
+pub struct PlayTest {
+    child: Child,
+    readers: Vec<JoinHandle<()>>,
+    pub log: Arc<Mutex<Vec<LogLine>>>,
+    pub status: Option<ExitStatus>,
+}
+
+impl PlayTest {
+    /// Starts `executable` with `level` as its argument, calling `on_output`
+    /// whenever a new line is logged.
+    pub fn spawn(
+        executable: &Path,
+        level: &Path,
+        on_output: impl Fn() + Clone + Send + 'static,
+    ) -> io::Result<Self> {
+        let mut command = Command::new(executable);
+        command
+            // It would be looked for from the game's directory otherwise
+            .arg(path::absolute(level)?)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // The game looks for its assets relative to the working directory
+        if let Some(directory) = executable.parent().filter(|dir| dir.is_dir()) {
+            command.current_dir(directory);
+        }
+        let mut child = command.spawn()?;
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let readers = vec![
+            Self::read_lines(child.stdout.take(), false, &log, on_output.clone()),
+            Self::read_lines(child.stderr.take(), true, &log, on_output),
+        ];
+
+        Ok(Self {
+            child,
+            readers: readers.into_iter().flatten().collect(),
+            log,
+            status: None,
+        })
+    }
+
+    fn read_lines(
+        stream: Option<impl Read + Send + 'static>,
+        stderr: bool,
+        log: &Arc<Mutex<Vec<LogLine>>>,
+        on_output: impl Fn() + Send + 'static,
+    ) -> Option<JoinHandle<()>> {
+        let log = log.clone();
+        stream.map(|stream| {
+            thread::spawn(move || {
+                for text in BufReader::new(stream).lines().map_while(Result::ok) {
+                    log.lock().unwrap().push(LogLine { stderr, text });
+                    on_output();
+                }
+            })
+        })
+    }
+
+    /// Checks whether the game has exited, returning its exit status if so.
+    pub fn poll(&mut self) -> Option<ExitStatus> {
+        if self.status.is_none() {
+            self.status = self.child.try_wait().ok().flatten();
+        }
+        self.status
+    }
+
+    /// Blocks until the game exits and all of its output has been logged.
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        let status = self.child.wait()?;
+        for reader in self.readers.drain(..) {
+            let _ = reader.join();
+        }
+        self.status = Some(status);
+        Ok(status)
+    }
+
+    pub fn kill(&mut self) {
+        if self.poll().is_none() {
+            let _ = self.child.kill();
+            let _ = self.wait();
+        }
+    }
+}
+
+impl Drop for PlayTest {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+#[cfg(all(test, unix))]
+#[test]
+fn captures_output() {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    let directory = std::env::temp_dir().join(format!("manifest-play-test-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let executable = directory.join("fake-game");
+    fs::write(
+        &executable,
+        "#!/bin/sh\n[ -f \"$1\" ] && echo \"loading $1\"\necho 'missing song' >&2\nexit 3\n",
+    )
+    .unwrap();
+    fs::set_permissions(&executable, fs::Permissions::from_mode(0o755)).unwrap();
+
+    // Relative to the test's working directory, not the game's
+    let level = Path::new("Cargo.toml");
+    let mut play_test = PlayTest::spawn(&executable, level, || {}).unwrap();
+    assert_eq!(play_test.wait().unwrap().code(), Some(3));
+
+    let log = play_test.log.lock().unwrap();
+    let expected = format!("loading {}", path::absolute(level).unwrap().display());
+    assert!(log.iter().any(|line| !line.stderr && line.text == expected));
+    assert!(log
+        .iter()
+        .any(|line| line.stderr && line.text == "missing song"));
+    drop(log);
+    fs::remove_dir_all(directory).unwrap();
+}
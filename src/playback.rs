@@ -0,0 +1,194 @@
+use std::time::Instant;
+
+use egui::{Key, Slider};
+
+use crate::{
+    audio::{default_backend, Backend, Song},
+    project::Project,
+};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum State {
+    #[default]
+    Stopped,
+    Playing,
+    Paused,
+}
+
+/// Transport for previewing a level in time with its BPM and song.
+pub struct Playback {
+    pub state: State,
+    /// Position in beats from the start of the level.
+    pub beat: f32,
+    /// When playing last started and from which beat, so the preview keeps
+    /// time with the song rather than accumulating frame times.
+    started: Option<(Instant, f32)>,
+    song: Option<Song>,
+    audio: Box<dyn Backend>,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self::with_backend(default_backend())
+    }
+}
+
+impl Playback {
+    pub fn with_backend(audio: Box<dyn Backend>) -> Self {
+        Self {
+            state: State::Stopped,
+            beat: 0.0,
+            started: None,
+            song: None,
+            audio,
+        }
+    }
+
+    /// Whether the canvas should show the preview rather than the editor view.
+    pub fn is_active(&self) -> bool {
+        self.state != State::Stopped
+    }
+
+    pub fn song(&self) -> Option<&Song> {
+        self.song.as_ref()
+    }
+
+    pub fn set_song(&mut self, song: Option<Song>, project: &Project) {
+        self.song = song;
+        self.audio.stop();
+        if self.state == State::Playing {
+            self.play(project);
+        }
+    }
+
+    pub fn play(&mut self, project: &Project) {
+        self.state = State::Playing;
+        self.started = Some((Instant::now(), self.beat));
+        if let Some(song) = &self.song {
+            self.audio
+                .play(song, self.beat * project.header.beat_length());
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if self.state == State::Playing {
+            self.state = State::Paused;
+            self.started = None;
+            self.audio.stop();
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.state = State::Stopped;
+        self.beat = 0.0;
+        self.started = None;
+        self.audio.stop();
+    }
+
+    /// Pauses if playing, or plays otherwise.
+    pub fn toggle(&mut self, project: &Project) {
+        if self.state == State::Playing {
+            self.pause();
+        } else {
+            self.play(project);
+        }
+    }
+
+    /// Jumps to `beat`, keeping the song in step if playing.
+    pub fn seek(&mut self, beat: f32, project: &Project) {
+        self.beat = beat.clamp(0.0, project.duration() as f32);
+        if self.state == State::Playing {
+            self.play(project);
+        } else if self.state == State::Stopped {
+            self.state = State::Paused;
+        }
+    }
+
+    /// Moves the preview along to the current time, stopping at the end of the level.
+    pub fn update(&mut self, project: &Project) {
+        if let Some((started, from)) = self.started {
+            self.beat = from + started.elapsed().as_secs_f32() / project.header.beat_length();
+            if self.beat >= project.duration() as f32 {
+                self.stop();
+            }
+        }
+    }
+
+    /// Shows the transport controls, with space to toggle playing.
+    pub fn ui(&mut self, ui: &mut egui::Ui, project: &Project) {
+        let toggle = ui.memory(|mem| mem.focus().is_none())
+            && ui.input(|input| input.key_pressed(Key::Space));
+        let bar = u32::from(project.header.time_signature_top.get());
+        let beat = self.beat.floor() as u32;
+        ui.horizontal(|ui| {
+            if ui.button("⏮").on_hover_text("Previous bar").clicked() {
+                let previous = beat.saturating_sub(1) / bar * bar;
+                self.seek(previous as f32, project);
+            }
+            if self.state == State::Playing {
+                if ui.button("⏸ Pause").clicked() || toggle {
+                    self.pause();
+                }
+            } else if ui.button("▶ Play").clicked() || toggle {
+                self.play(project);
+            }
+            if ui
+                .add_enabled(self.is_active(), egui::Button::new("⏹ Stop"))
+                .clicked()
+            {
+                self.stop();
+            }
+            if ui.button("⏭").on_hover_text("Next bar").clicked() {
+                self.seek(((beat / bar + 1) * bar) as f32, project);
+            }
+
+            let mut seek_to = self.beat;
+            if ui
+                .add(Slider::new(&mut seek_to, 0.0..=project.duration() as f32).show_value(false))
+                .changed()
+            {
+                self.seek(seek_to, project);
+            }
+
+            let offset = u32::from(project.header.effective_offset().get());
+            ui.label(format!(
+                "Bar {}, beat {} ({} / {} beats)",
+                beat / bar + 1,
+                beat % bar + 1,
+                beat,
+                project.duration(),
+            ));
+            if self.is_active() && self.beat < offset as f32 {
+                ui.weak(format!("Count-in: {}", offset - beat));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn song_follows_transport() {
+    use crate::audio::NullBackend;
+
+    let path = std::env::temp_dir().join(format!("manifest-transport-{}.wav", std::process::id()));
+    crate::audio::write_sine_wav(&path, 220.0, 1.0);
+    let song = Song::load(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    let audio = NullBackend::default();
+    let playing = audio.playing.clone();
+    let project = Project::default();
+    let mut playback = Playback::with_backend(Box::new(audio));
+    playback.set_song(Some(song), &project);
+
+    // At 120 BPM in 4/4 a beat lasts half a second
+    playback.seek(8.0, &project);
+    assert_eq!(playing.get(), None);
+    playback.play(&project);
+    assert_eq!(playing.get(), Some(4.0));
+    playback.seek(2.0, &project);
+    assert_eq!(playing.get(), Some(1.0));
+    playback.pause();
+    assert_eq!(playing.get(), None);
+    assert_eq!(playback.beat, 2.0);
+}
@@ -1,6 +1,11 @@
 use std::{
-    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    fs::{read_to_string, File},
+    io::{self, Write},
     num::{NonZeroU16, NonZeroU8},
+    ops::Range,
+    path::{Path, PathBuf},
 };
 
 use eframe::{emath::RectTransform, epaint::RectShape};
@@ -8,12 +13,15 @@ use egui::{Pos2, Rect, Vec2};
 use glam::uvec2;
 use json::{object::Object, Array, JsonValue};
 
-use crate::shape::Shape;
+use crate::{audio::Song, shape::Shape};
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Project {
     pub header: Header,
     pub shapes: Vec<Shape>,
+    /// The song played along with the level. The game finds it by looking
+    /// beside the level, so it isn't written to the file.
+    pub song: Option<PathBuf>,
 }
 
 impl Project {
@@ -28,13 +36,23 @@ impl Project {
             egui::Sense::click_and_drag(),
         );
 
-        let to_screen = RectTransform::from_to(
-            Rect::from_min_size(Pos2::ZERO, response.rect.square_proportions() * 17.0),
-            response.rect,
-        );
+        let to_screen = Self::to_screen(response.rect);
 
         response.mark_changed();
 
+        let background = self
+            .header
+            .color_table
+            .get(usize::from(self.header.bg_color))
+            .map_or(egui::Color32::BLACK, |color| {
+                egui::Color32::from_rgb(color[0], color[1], color[2])
+            });
+        painter.rect_filled(
+            Rect::from_min_max(to_screen * Pos2::ZERO, to_screen * Pos2::new(15.0, 15.0)),
+            egui::Rounding::none(),
+            background,
+        );
+
         let shapes = self
             .shapes
             .iter()
@@ -60,57 +78,222 @@ impl Project {
         response
     }
 
-    pub fn from_json(json: JsonValue) -> Option<Self> {
-        let header = &json[0].entries().collect::<HashMap<_, _>>();
+    /// Moves the shapes at `indices`, which must be in order, so they sit
+    /// together from `target` among the shapes left, returning where they ended up.
+    pub fn move_shapes(&mut self, indices: &[usize], target: usize) -> Range<usize> {
+        let mut moved = indices
+            .iter()
+            .rev()
+            .map(|i| self.shapes.remove(*i))
+            .collect::<Vec<_>>();
+        moved.reverse();
+        let target = target.min(self.shapes.len());
+        let range = target..target + moved.len();
+        self.shapes.splice(target..target, moved);
+        range
+    }
+
+    /// Maps grid coordinates to the screen `rect` a project is drawn in.
+    pub fn to_screen(rect: Rect) -> RectTransform {
+        RectTransform::from_to(
+            Rect::from_min_size(Pos2::ZERO, rect.square_proportions() * 17.0),
+            rect,
+        )
+    }
+
+    /// Finds the topmost of the first `shape_count` shapes covering `point` in grid coordinates.
+    pub fn shape_at(&self, point: Pos2, shape_count: usize) -> Option<usize> {
+        self.shapes
+            .iter()
+            .take(shape_count)
+            .rposition(|shape| shape.contains(point))
+    }
+
+    /// Lays out when each shape plays, in beats from the start of the song.
+    ///
+    /// Each shape gets one beat per move, rounded up to whole bars.
+    pub fn segments(&self) -> Vec<Segment> {
+        let bar = u32::from(self.header.time_signature_top.get());
+        let mut start = u32::from(self.header.effective_offset().get());
+        self.shapes
+            .iter()
+            .enumerate()
+            .map(|(shape, moves)| {
+                let steps = moves.moves.as_ref().map_or(0, Vec::len) as u32;
+                let length = steps.max(1).div_ceil(bar) * bar;
+                let segment = Segment {
+                    shape,
+                    start,
+                    length,
+                };
+                start += length;
+                segment
+            })
+            .collect()
+    }
+
+    /// Length of the level in beats, including the offset before the first shape.
+    pub fn duration(&self) -> u32 {
+        self.segments().last().map_or_else(
+            || u32::from(self.header.effective_offset().get()),
+            |segment| segment.start + segment.length,
+        )
+    }
+
+    /// The shapes on screen `beat` beats into the level, with their moves animated.
+    pub fn at_beat(&self, beat: f32) -> Self {
+        let shapes = self
+            .segments()
+            .into_iter()
+            .take_while(|segment| segment.start as f32 <= beat)
+            .map(|segment| {
+                let mut shape = self.shapes[segment.shape].clone();
+                let progress = beat - segment.start as f32;
+                for (i, step) in shape
+                    .moves
+                    .clone()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .enumerate()
+                {
+                    let amount = (progress - i as f32).clamp(0.0, 1.0);
+                    if amount == 0.0 {
+                        break;
+                    }
+                    shape.apply_move(step, amount);
+                }
+                shape
+            })
+            .collect();
+
+        Self {
+            header: self.header.clone(),
+            shapes,
+            song: self.song.clone(),
+        }
+    }
+
+    /// The beat when step `step` of shape `shape`'s sequence finishes, or when
+    /// the shape appears if it has no sequence.
+    pub fn step_beat(&self, shape: usize, step: usize) -> Option<f32> {
+        let segment = self.segments().into_iter().nth(shape)?;
+        let steps = self.shapes[shape].moves.as_ref().map_or(0, Vec::len);
+        Some((segment.start + (step + 1).min(steps) as u32) as f32)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        let text = read_to_string(path).map_err(|err| LoadError::new("", err.to_string()))?;
+        let json =
+            json::parse(&text).map_err(|err| LoadError::new("", format!("invalid JSON: {err}")))?;
+        Ok(Self {
+            song: Song::find_beside(path),
+            ..Self::from_json(json)?
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        write!(file, "{}", json::stringify_pretty(self.as_json(), 4))
+    }
+
+    pub fn from_json(json: JsonValue) -> Result<Self, LoadError> {
+        if !json.is_array() {
+            return Err(LoadError::new(
+                "",
+                "expected an array of the header followed by shapes",
+            ));
+        }
+        let header = &json[0];
+        if !header.is_object() {
+            return Err(LoadError::new("[0]", "expected the level header object"));
+        }
         macro_rules! header_item {
             ($i: ident) => {
-                header[stringify!($i)].to_string()
+                get_field(header, "[0]", stringify!($i), "a string", JsonValue::as_str)?.to_owned()
             };
         }
 
         let shapes = json
             .members()
+            .enumerate()
             .skip(1)
-            .map(Shape::from_json)
-            .collect::<Vec<_>>();
+            .map(|(i, shape)| Shape::from_json(shape, &format!("[{i}]")))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        Some(Self {
+        let default = Header::default();
+        let time_signature_top = get_optional_field(
+            header,
+            "[0]",
+            "time_signature_top",
+            "a positive whole number",
+            |top| top.as_u8().and_then(NonZeroU8::new),
+        )?
+        .unwrap_or(default.time_signature_top);
+        let time_signature_bottom = get_optional_field(
+            header,
+            "[0]",
+            "time_signature_bottom",
+            "a positive whole number",
+            |bottom| bottom.as_u8().and_then(NonZeroU8::new),
+        )?
+        .unwrap_or(default.time_signature_bottom);
+        let default_offset = Header::offset_for(time_signature_top, time_signature_bottom);
+        let offset = get_optional_field(
+            header,
+            "[0]",
+            "offset",
+            "a positive whole number",
+            |offset| offset.as_u16().and_then(NonZeroU16::new),
+        )?
+        .unwrap_or(default_offset);
+
+        Ok(Self {
             header: Header {
                 name: header_item!(name),
                 song_author: header_item!(song_author),
                 level_author: header_item!(level_author),
                 genre: header_item!(genre),
-                bpm: NonZeroU16::new(header["bpm"].as_u16()?)?,
-                bg_color: header["bg_color"].as_u8()?,
-                background_effect: header["background_effect"].to_string(),
-                color_table: header
-                    .get("color_table")
-                    .map_or(Header::default().color_table, |list| {
-                        list.members()
-                            .map(|color| {
-                                color
-                                    .to_string()
-                                    .chars()
-                                    .skip(1)
-                                    .collect::<Vec<_>>()
-                                    .chunks((color.to_string().len() - 1) / 3)
-                                    .map(|text| {
-                                        let multiplier = if text.len() == 1 { 0x11 } else { 0x1 };
-                                        u8::from_str_radix(&String::from_iter(text.iter()), 16)
-                                            .unwrap()
-                                            * multiplier
-                                    })
-                                    .collect::<Vec<_>>()
-                                    .try_into()
-                                    .unwrap()
-                            })
-                            .collect::<Vec<_>>()
-                            .try_into()
-                            .unwrap()
-                    }),
-                ..Default::default()
+                bpm: get_field(header, "[0]", "bpm", "a positive whole number", |bpm| {
+                    bpm.as_u16().and_then(NonZeroU16::new)
+                })?,
+                bg_color: get_field(header, "[0]", "bg_color", "a color index 0–15", |color| {
+                    color.as_u8().filter(|color| *color < 16)
+                })?,
+                background_effect: header_item!(background_effect),
+                color_table: match &header["color_table"] {
+                    JsonValue::Null => default.color_table,
+                    JsonValue::Array(list) => list
+                        .iter()
+                        .enumerate()
+                        .map(|(i, color)| parse_color(color, &format!("[0].color_table[{i}]")))
+                        .collect::<Result<Vec<_>, _>>()?
+                        .try_into()
+                        .map_err(|colors: Vec<_>| {
+                            LoadError::new(
+                                "[0].color_table",
+                                format!("expected 16 colors, found {}", colors.len()),
+                            )
+                        })?,
+                    other => {
+                        return Err(LoadError::new(
+                            "[0].color_table",
+                            format!("expected a list of colors, found {other}"),
+                        ))
+                    }
+                },
+                extra: header
+                    .entries()
+                    .filter(|(key, _)| !Header::KEYS.contains(key))
+                    .map(|(key, value)| (key, value.clone()))
+                    .collect(),
+                key_order: KeyOrder::of(header),
+                offset,
+                manual_offset: offset != default_offset,
+                time_signature_top,
+                time_signature_bottom,
             },
             shapes,
+            song: None,
         })
     }
 
@@ -121,7 +304,152 @@ impl Project {
     }
 }
 
+/// A stretch of the level in which one shape plays out its moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Segment {
+    /// Index into [`Project::shapes`].
+    pub shape: usize,
+    /// Beat this segment starts on.
+    pub start: u32,
+    /// Length in beats.
+    pub length: u32,
+}
+
+/// Why a level file couldn't be loaded, and where in the JSON the problem is.
 #[derive(Debug)]
+pub struct LoadError {
+    /// Path to the offending value, such as `[12].shape`, or empty if the
+    /// whole file is at fault.
+    pub path: String,
+    pub reason: String,
+}
+
+impl LoadError {
+    pub fn new(path: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.reason)
+        } else {
+            write!(f, "{}: {}", self.path, self.reason)
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+/// The order an object's keys were loaded in, so a save writes them back
+/// the same way. Only formatting, so it never makes two values unequal.
+#[derive(Clone, Debug, Default)]
+pub struct KeyOrder(Vec<String>);
+
+impl KeyOrder {
+    pub fn of(object: &JsonValue) -> Self {
+        Self(object.entries().map(|(key, _)| key.to_owned()).collect())
+    }
+
+    /// Rearranges `object` into this order, leaving keys that weren't there at the end.
+    pub fn apply(&self, object: Object) -> Object {
+        if self.0.is_empty() {
+            return object;
+        }
+        let mut ordered = Object::with_capacity(object.len());
+        for key in &self.0 {
+            if let Some(value) = object.get(key) {
+                ordered.insert(key, value.clone());
+            }
+        }
+        for (key, value) in object.iter() {
+            if ordered.get(key).is_none() {
+                ordered.insert(key, value.clone());
+            }
+        }
+        ordered
+    }
+}
+
+impl PartialEq for KeyOrder {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// Reads `object[key]` with `get`, reporting what was `expected` if it's missing or the wrong type.
+pub fn get_field<'a, T>(
+    object: &'a JsonValue,
+    path: &str,
+    key: &str,
+    expected: &str,
+    get: impl FnOnce(&'a JsonValue) -> Option<T>,
+) -> Result<T, LoadError> {
+    let value = &object[key];
+    get(value).ok_or_else(|| {
+        LoadError::new(
+            format!("{path}.{key}"),
+            if value.is_null() {
+                format!("missing, expected {expected}")
+            } else {
+                format!("expected {expected}, found {value}")
+            },
+        )
+    })
+}
+
+/// Like [`get_field`], but `object[key]` may be left out.
+pub fn get_optional_field<'a, T>(
+    object: &'a JsonValue,
+    path: &str,
+    key: &str,
+    expected: &str,
+    get: impl FnOnce(&'a JsonValue) -> Option<T>,
+) -> Result<Option<T>, LoadError> {
+    if object[key].is_null() {
+        Ok(None)
+    } else {
+        get_field(object, path, key, expected, get).map(Some)
+    }
+}
+
+/// Parses a `#RGB` or `#RRGGBB` color table entry.
+fn parse_color(value: &JsonValue, path: &str) -> Result<[u8; 3], LoadError> {
+    let error = || {
+        LoadError::new(
+            path,
+            format!("expected a #RGB or #RRGGBB color, found {value}"),
+        )
+    };
+    let hex = value
+        .as_str()
+        .and_then(|text| text.strip_prefix('#'))
+        .ok_or_else(error)?;
+    let width = match hex.len() {
+        3 => 1,
+        6 => 2,
+        _ => return Err(error()),
+    };
+
+    let mut color = [0; 3];
+    for (i, channel) in color.iter_mut().enumerate() {
+        let digits = hex.get(i * width..(i + 1) * width).ok_or_else(error)?;
+        let multiplier = if width == 1 { 0x11 } else { 0x1 };
+        *channel = u8::from_str_radix(digits, 16).map_err(|_| error())? * multiplier;
+    }
+    Ok(color)
+}
+
+/// Values of [`Header::background_effect`] offered in the editor. These haven't
+/// been checked against Open Manifold's own list, so other values are kept as
+/// they are rather than rejected.
+pub const BACKGROUND_EFFECTS: [&str; 5] = ["none", "checkerboard", "scroll", "wave", "stars"];
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Header {
     pub name: String,
     pub genre: String,
@@ -136,13 +464,58 @@ pub struct Header {
     pub bg_color: u8,
     pub background_effect: String,
     pub color_table: [[u8; 3]; 16],
+    /// Keys this editor doesn't know about, kept so they survive a save.
+    pub extra: Object,
+    /// The order keys were in when loaded, to write them back the same way.
+    pub key_order: KeyOrder,
 }
 
 impl Header {
+    const KEYS: [&str; 11] = [
+        "name",
+        "genre",
+        "level_author",
+        "song_author",
+        "background_effect",
+        "bpm",
+        "offset",
+        "time_signature_top",
+        "time_signature_bottom",
+        "bg_color",
+        "color_table",
+    ];
+
+    /// The offset the game uses for this time signature unless one is set manually.
+    pub fn default_offset(&self) -> NonZeroU16 {
+        Self::offset_for(self.time_signature_top, self.time_signature_bottom)
+    }
+
+    /// The offset the level actually starts with.
+    pub fn effective_offset(&self) -> NonZeroU16 {
+        if self.manual_offset {
+            self.offset
+        } else {
+            self.default_offset()
+        }
+    }
+
+    /// Two bars' worth of the smallest note, stopping at the largest offset a
+    /// level can hold for very long bars.
+    fn offset_for(top: NonZeroU8, bottom: NonZeroU8) -> NonZeroU16 {
+        NonZeroU16::from(top)
+            .saturating_mul(bottom.into())
+            .saturating_mul(NonZeroU16::MIN.saturating_add(1))
+    }
+
+    /// How long one beat lasts in seconds, with the BPM counted in quarter notes.
+    pub fn beat_length(&self) -> f32 {
+        60.0 / f32::from(self.bpm.get()) * 4.0 / f32::from(self.time_signature_bottom.get())
+    }
+
     fn as_json(&self) -> Object {
         // TODO Could be easier to just store as an Object to begin with
         // so no cloning is necessary when creating the object here
-        let mut to_ret = Object::with_capacity(11);
+        let mut to_ret = Object::with_capacity(Self::KEYS.len() + self.extra.len());
         macro_rules! add_str {
             {$($i: ident),*} => {
                 $(
@@ -158,7 +531,9 @@ impl Header {
             };
         }
         add_str!(name, genre, level_author, song_author, background_effect);
-        add_num!(bpm, offset, time_signature_top, time_signature_bottom);
+        add_num!(bpm);
+        to_ret.insert("offset", self.effective_offset().get().into());
+        add_num!(time_signature_top, time_signature_bottom);
         to_ret.insert("bg_color", self.bg_color.into());
         to_ret.insert(
             "color_table",
@@ -168,7 +543,10 @@ impl Header {
                 .collect::<Vec<_>>()
                 .into(),
         );
-        to_ret
+        for (key, value) in self.extra.iter() {
+            to_ret.insert(key, value.clone());
+        }
+        self.key_order.apply(to_ret)
     }
 }
 
@@ -184,6 +562,206 @@ fn header_as_json() {
     assert_eq!(header.as_json().pretty(4), "{\n    \"name\": \"Test\",\n    \"genre\": \"Maybe\",\n    \"level_author\": \"Anonymous\",\n    \"song_author\": \"Anonymous\",\n    \"background_effect\": \"none\",\n    \"bpm\": 120,\n    \"offset\": 32,\n    \"time_signature_top\": 4,\n    \"time_signature_bottom\": 4,\n    \"bg_color\": 15,\n    \"color_table\": [\n        \"#FFFFFF\",\n        \"#0000FF\",\n        \"#00FF00\",\n        \"#00FFFF\",\n        \"#FF0000\",\n        \"#FF00FF\",\n        \"#FF6600\",\n        \"#AAAAAA\",\n        \"#666666\",\n        \"#6666FF\",\n        \"#66FF66\",\n        \"#66FFFF\",\n        \"#FF6666\",\n        \"#FF66FF\",\n        \"#FFFF22\",\n        \"#000000\"\n    ]\n}");
 }
 
+#[cfg(test)]
+#[test]
+fn timeline() {
+    use crate::shape::Move;
+
+    let project = Project {
+        shapes: vec![
+            Shape::default(),
+            Shape {
+                moves: Some(vec![Move::Up; 6]),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+    let segments = project.segments();
+    assert_eq!(segments[0].start, 32);
+    assert_eq!(segments[1].start, 36);
+    assert_eq!(segments[1].length, 8);
+    assert_eq!(project.duration(), 44);
+
+    assert!(project.at_beat(31.0).shapes.is_empty());
+    let frame = project.at_beat(37.5);
+    assert_eq!(frame.shapes.len(), 2);
+    assert_eq!(frame.shapes[1].pos.y, 5.5);
+    assert_eq!(project.at_beat(50.0).shapes[1].pos.y, 1.0);
+
+    assert_eq!(project.step_beat(0, 0), Some(32.0));
+    assert_eq!(project.step_beat(1, 1), Some(38.0));
+    assert_eq!(project.step_beat(1, 10), Some(42.0));
+    assert_eq!(project.step_beat(2, 0), None);
+}
+
+#[cfg(test)]
+#[test]
+fn reorder() {
+    let mut project = Project {
+        shapes: (0..5)
+            .map(|color| Shape {
+                color,
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let colors = |project: &Project| {
+        project
+            .shapes
+            .iter()
+            .map(|shape| shape.color)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(project.move_shapes(&[1, 3], 0), 0..2);
+    assert_eq!(colors(&project), [1, 3, 0, 2, 4]);
+    assert_eq!(project.move_shapes(&[0, 1], 10), 3..5);
+    assert_eq!(colors(&project), [0, 2, 4, 1, 3]);
+    assert_eq!(project.move_shapes(&[2], 1), 1..2);
+    assert_eq!(colors(&project), [0, 4, 2, 1, 3]);
+}
+
+#[cfg(test)]
+#[test]
+fn header_timing_round_trip() {
+    let round_trip = |header: Header| {
+        let project = Project {
+            header,
+            ..Default::default()
+        };
+        Project::from_json(project.as_json().into()).unwrap().header
+    };
+
+    let waltz = Header {
+        time_signature_top: NonZeroU8::new(3).unwrap(),
+        offset: NonZeroU16::new(24).unwrap(),
+        ..Default::default()
+    };
+    let loaded = round_trip(waltz.clone());
+    assert_eq!(loaded, waltz);
+    assert!(!loaded.manual_offset);
+
+    let late_start = Header {
+        time_signature_bottom: NonZeroU8::new(8).unwrap(),
+        offset: NonZeroU16::new(10).unwrap(),
+        manual_offset: true,
+        ..Default::default()
+    };
+    let loaded = round_trip(late_start.clone());
+    assert_eq!(loaded, late_start);
+    assert!(loaded.manual_offset);
+
+    // Turning the manual offset off only sets it aside
+    let mut paused = Header {
+        manual_offset: false,
+        ..late_start
+    };
+    assert_eq!(paused.effective_offset(), paused.default_offset());
+    assert_eq!(round_trip(paused.clone()).offset, paused.default_offset());
+    paused.manual_offset = true;
+    assert_eq!(paused.effective_offset().get(), 10);
+
+    let long_bars = Header {
+        time_signature_top: NonZeroU8::new(200).unwrap(),
+        time_signature_bottom: NonZeroU8::new(200).unwrap(),
+        offset: NonZeroU16::MAX,
+        ..Default::default()
+    };
+    assert_eq!(long_bars.default_offset(), NonZeroU16::MAX);
+    assert_eq!(round_trip(long_bars.clone()), long_bars);
+}
+
+#[cfg(test)]
+#[test]
+fn load_error_paths() {
+    let error = |text: &str| Project::from_json(json::parse(text).unwrap()).unwrap_err();
+
+    assert_eq!(error("{}").path, "");
+    assert_eq!(error("[1]").path, "[0]");
+
+    let header = Header::default().as_json().dump();
+    let shape_error = error(&format!(
+        r#"[{header}, {{"shape": 0, "color": 0, "x": 1, "y": 1, "scale": 1}}, {{"shape": 7, "color": 0, "x": 1, "y": 1, "scale": 1}}]"#
+    ));
+    assert_eq!(shape_error.path, "[2].shape");
+    assert_eq!(shape_error.to_string(), "[2].shape: unknown shape type 7");
+
+    let color_error = error(&format!(
+        r#"[{header}, {{"shape": 0, "color": 16, "x": 1, "y": 1, "scale": 1}}]"#
+    ));
+    assert_eq!(
+        color_error.to_string(),
+        "[1].color: expected a color index 0–15, found 16"
+    );
+    let header = header.replace("\"bg_color\":15", "\"bg_color\":20");
+    assert_eq!(error(&format!("[{header}]")).path, "[0].bg_color");
+}
+
+#[cfg(test)]
+#[test]
+fn level_round_trip() {
+    let level = json::parse(
+        r##"[
+            {
+                "name": "Round Trip",
+                "genre": "Techno",
+                "song_file": "song.ogg",
+                "level_author": "Someone",
+                "song_author": "Someone Else",
+                "background_effect": "none",
+                "bpm": 140,
+                "offset": 32,
+                "time_signature_top": 4,
+                "time_signature_bottom": 4,
+                "bg_color": 15,
+                "color_table": [
+                    "#FFFFFF", "#0000FF", "#00FF00", "#00FFFF",
+                    "#FF0000", "#FF00FF", "#FF6600", "#AAAAAA",
+                    "#666666", "#6666FF", "#66FF66", "#66FFFF",
+                    "#FF6666", "#FF66FF", "#FFFF22", "#000000"
+                ],
+                "difficulty": { "stars": 3, "tags": ["fast"] }
+            },
+            { "shape": 0, "color": 1, "x": 7, "y": 7, "scale": 1 },
+            {
+                "shape": 1,
+                "color": 4,
+                "x": 3,
+                "y": 10,
+                "hint": "watch the square",
+                "scale": 3,
+                "sequence": "UU.LR"
+            },
+            {
+                "shape": 2,
+                "color": 2,
+                "x": 12,
+                "y": 2,
+                "scale": 2,
+                "auto_shapes": [
+                    { "shape": 0, "color": 3, "x": 1, "y": 1, "scale": 1, "spin": true }
+                ]
+            }
+        ]"##,
+    )
+    .unwrap();
+
+    let project = Project::from_json(level.clone()).unwrap();
+    let saved = JsonValue::from(project.as_json());
+    assert_eq!(saved, level);
+
+    // Object's == ignores order, so check where the unknown keys ended up
+    fn keys(value: &JsonValue) -> Vec<&str> {
+        value.entries().map(|(key, _)| key).collect()
+    }
+    assert_eq!(keys(&saved[0]), keys(&level[0]));
+    assert_eq!(keys(&saved[0])[2], "song_file");
+    assert_eq!(keys(&saved[2]), keys(&level[2]));
+    assert_eq!(keys(&saved[2])[4], "hint");
+}
+
 impl Default for Header {
     fn default() -> Self {
         let time_signature_top = NonZeroU8::new(4).unwrap();
@@ -195,10 +773,7 @@ impl Default for Header {
             level_author: "Anonymous".to_owned(),
             song_author: "Anonymous".to_owned(),
             bpm: NonZeroU16::new(120).unwrap(),
-            offset: NonZeroU16::new(
-                u16::from(time_signature_top.get()) * u16::from(time_signature_bottom.get()) * 2,
-            )
-            .unwrap(),
+            offset: Self::offset_for(time_signature_top, time_signature_bottom),
             manual_offset: false,
             time_signature_top,
             time_signature_bottom,
@@ -222,6 +797,8 @@ impl Default for Header {
                 [0xFF, 0xFF, 0x22],
                 [0x00, 0x00, 0x00],
             ],
+            extra: Object::new(),
+            key_order: KeyOrder::default(),
         }
     }
 }
@@ -0,0 +1,233 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    ops::RangeInclusive,
+    path::Path,
+};
+
+use egui::Pos2;
+
+use crate::project::Project;
+
+/// Samples per pixel along each axis, for smoother edges.
+const SUPERSAMPLING: u32 = 2;
+/// Width and height of exported images unless asked otherwise.
+pub const DEFAULT_SIZE: u32 = 480;
+/// Largest width and height the command line will draw, to keep memory in check.
+pub const MAX_SIZE: u32 = 4096;
+/// Frames drawn per beat in animations.
+const FRAMES_PER_BEAT: u32 = 4;
+
+/// An RGB image drawn without a GPU, for previews outside the editor.
+pub struct Image {
+    pub size: u32,
+    pub pixels: Vec<[u8; 3]>,
+}
+
+impl Image {
+    /// Draws the first `shape_count` shapes of `project` and the grid, as
+    /// [`Project::draw`] does, on a square image `size` pixels across.
+    pub fn render(project: &Project, shape_count: usize, size: u32) -> Self {
+        let color_table = &project.header.color_table;
+        let background = color_table
+            .get(usize::from(project.header.bg_color))
+            .copied()
+            .unwrap_or_default();
+
+        let samples = size * SUPERSAMPLING;
+        let scale = samples as f32 / 15.0;
+        let mut buffer = vec![background; (samples * samples) as usize];
+        let shapes = project
+            .shapes
+            .iter()
+            .take(shape_count)
+            .flat_map(|shape| std::iter::once(shape).chain(&shape.auto_shapes));
+        for shape in shapes {
+            let color = color_table.get(shape.color).copied().unwrap_or_default();
+            let bounds = shape.bounds();
+            let pixel_range = |min: f32, max: f32| {
+                (min * scale).floor().max(0.0) as u32..((max * scale).ceil() as u32).min(samples)
+            };
+            for y in pixel_range(bounds.min.y, bounds.max.y) {
+                for x in pixel_range(bounds.min.x, bounds.max.x) {
+                    let point = Pos2::new((x as f32 + 0.5) / scale, (y as f32 + 0.5) / scale);
+                    if shape.contains(point) {
+                        buffer[(y * samples + x) as usize] = color;
+                    }
+                }
+            }
+        }
+
+        let mut pixels = (0..size * size)
+            .map(|i| {
+                let (x, y) = (i % size * SUPERSAMPLING, i / size * SUPERSAMPLING);
+                let mut sum = [0_u32; 3];
+                for sample_y in y..y + SUPERSAMPLING {
+                    for sample_x in x..x + SUPERSAMPLING {
+                        let sample = buffer[(sample_y * samples + sample_x) as usize];
+                        for (total, channel) in sum.iter_mut().zip(sample) {
+                            *total += u32::from(channel);
+                        }
+                    }
+                }
+                sum.map(|total| (total / (SUPERSAMPLING * SUPERSAMPLING)) as u8)
+            })
+            .collect::<Vec<_>>();
+
+        for line in 0..=15 {
+            let offset = ((line as f32 * size as f32 / 15.0) as u32).min(size - 1);
+            for along in 0..size {
+                pixels[(offset * size + along) as usize] = [0; 3];
+                pixels[(along * size + offset) as usize] = [0; 3];
+            }
+        }
+
+        Self { size, pixels }
+    }
+
+    /// Draws the level as it is `beat` beats in, with every move up to then applied.
+    pub fn at_beat(project: &Project, beat: f32, size: u32) -> Self {
+        Self::render(&project.at_beat(beat), usize::MAX, size)
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), png::EncodingError> {
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(File::create(path)?), self.size, self.size);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()?
+            .write_image_data(&self.pixels.concat())
+    }
+}
+
+/// A level played out from when the first shape appears to the end of its
+/// last sequence, paced to the level's tempo. Frames are only drawn as
+/// they're written, since a whole level of them can take a lot of memory.
+pub struct Animation<'a> {
+    project: &'a Project,
+    size: u32,
+    /// Frame numbers counted from the start of the level.
+    frames: RangeInclusive<u32>,
+    /// Seconds between frames.
+    pub frame_length: f32,
+}
+
+impl<'a> Animation<'a> {
+    pub fn new(project: &'a Project, size: u32) -> Self {
+        let start = project.header.effective_offset().get() as u32;
+        Self {
+            project,
+            size,
+            frames: start * FRAMES_PER_BEAT..=project.duration() * FRAMES_PER_BEAT,
+            frame_length: project.header.beat_length() / FRAMES_PER_BEAT as f32,
+        }
+    }
+
+    /// Draws each frame in turn.
+    pub fn frames(&self) -> impl Iterator<Item = Image> + '_ {
+        self.frames.clone().map(|frame| {
+            Image::at_beat(
+                self.project,
+                frame as f32 / FRAMES_PER_BEAT as f32,
+                self.size,
+            )
+        })
+    }
+
+    /// Writes a looping GIF.
+    pub fn save_gif(&self, path: &Path) -> Result<(), gif::EncodingError> {
+        let Ok(size) = u16::try_from(self.size) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "GIFs can be at most 65535 pixels across",
+            )
+            .into());
+        };
+        let mut encoder = gif::Encoder::new(BufWriter::new(File::create(path)?), size, size, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        // GIF delays are in hundredths of a second, so round each frame's
+        // start time rather than its length to keep in time with the song
+        let centiseconds =
+            |frame: usize| (frame as f64 * f64::from(self.frame_length) * 100.0).round() as u32;
+        for (i, image) in self.frames().enumerate() {
+            let mut frame = gif::Frame::from_rgb_speed(size, size, &image.pixels.concat(), 10);
+            let delay = centiseconds(i + 1) - centiseconds(i);
+            frame.delay = u16::try_from(delay).unwrap_or(u16::MAX);
+            encoder.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn render_shapes() {
+    use crate::shape::{Shape, ShapeType};
+    use egui::vec2;
+
+    let project = Project {
+        shapes: vec![
+            Shape {
+                ty: ShapeType::Square,
+                pos: vec2(0.0, 0.0),
+                color: 4,
+                ..Default::default()
+            },
+            Shape {
+                pos: vec2(7.0, 7.0),
+                size: 1.0,
+                color: 2,
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+    let image = Image::render(&project, usize::MAX, 150);
+    let pixel = |x: u32, y: u32| image.pixels[(y * image.size + x) as usize];
+
+    assert_eq!(pixel(5, 5), [0xFF, 0x00, 0x00]);
+    assert_eq!(pixel(75, 75), [0x00, 0xFF, 0x00]);
+    assert_eq!(pixel(140, 140), [0x00, 0x00, 0x00]);
+    assert_eq!(pixel(10, 5), [0x00, 0x00, 0x00], "grid line");
+
+    let first_only = Image::render(&project, 1, 150);
+    assert_eq!(
+        first_only.pixels[75 * 150 + 75],
+        project.header.color_table[15]
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn animate() {
+    use crate::shape::{Move, Shape};
+
+    let project = Project {
+        shapes: vec![Shape {
+            moves: Some(vec![Move::Right, Move::Down]),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let animation = Animation::new(&project, 30);
+    // 4 beats of the first bar, plus the final frame
+    assert_eq!(animation.frames().count(), 17);
+    assert_eq!(animation.frame_length, 0.125);
+
+    let path = std::env::temp_dir().join(format!("manifest-{}.gif", std::process::id()));
+    animation.save_gif(&path).unwrap();
+    let mut decoder = gif::DecodeOptions::new()
+        .read_info(File::open(&path).unwrap())
+        .unwrap();
+    let mut delays = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        delays.push(frame.delay);
+    }
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(delays.len(), 17);
+    assert_eq!(
+        delays.iter().map(|delay| u32::from(*delay)).sum::<u32>(),
+        213
+    );
+}
@@ -1,8 +1,10 @@
 use eframe::emath::RectTransform;
-use egui::{vec2, Color32, Pos2, Vec2};
+use egui::{vec2, Color32, Pos2, Rect, Vec2};
 use json::{object::Object, JsonValue};
 
-#[derive(Debug)]
+use crate::project::{get_field, KeyOrder, LoadError};
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Shape {
     pub pos: Vec2,
     pub size: f32,
@@ -10,11 +12,25 @@ pub struct Shape {
     pub color: usize,
     pub moves: Option<Vec<Move>>,
     pub auto_shapes: Vec<Shape>,
+    /// Keys this editor doesn't know about, kept so they survive a save.
+    pub extra: Object,
+    /// The order keys were in when loaded, to write them back the same way.
+    pub key_order: KeyOrder,
 }
 
 impl Shape {
+    const KEYS: [&str; 7] = [
+        "shape",
+        "color",
+        "x",
+        "y",
+        "scale",
+        "sequence",
+        "auto_shapes",
+    ];
+
     pub fn as_json(&self) -> Object {
-        let mut to_ret = Object::with_capacity(6);
+        let mut to_ret = Object::with_capacity(7 + self.extra.len());
         to_ret.insert(
             "shape",
             match self.ty {
@@ -28,30 +44,163 @@ impl Shape {
         to_ret.insert("x", self.pos.x.into());
         to_ret.insert("y", self.pos.y.into());
         to_ret.insert("scale", (self.size + 1.0).into());
-        to_ret
+        if let Some(moves) = &self.moves {
+            to_ret.insert(
+                "sequence",
+                moves
+                    .iter()
+                    .copied()
+                    .map(Move::as_char)
+                    .collect::<String>()
+                    .into(),
+            );
+        }
+        if !self.auto_shapes.is_empty() {
+            to_ret.insert(
+                "auto_shapes",
+                self.auto_shapes
+                    .iter()
+                    .map(Self::as_json)
+                    .map(JsonValue::from)
+                    .collect::<Vec<_>>()
+                    .into(),
+            );
+        }
+        for (key, value) in self.extra.iter() {
+            to_ret.insert(key, value.clone());
+        }
+        self.key_order.apply(to_ret)
     }
 
-    pub fn from_json(value: &JsonValue) -> Self {
-        Shape {
-            pos: vec2(value["x"].as_f32().unwrap(), value["y"].as_f32().unwrap()),
-            ty: match value["shape"].as_u8().unwrap() {
+    /// Reads a shape object, where `path` locates it in the level for error messages.
+    pub fn from_json(value: &JsonValue, path: &str) -> Result<Self, LoadError> {
+        if !value.is_object() {
+            return Err(LoadError::new(
+                path,
+                format!("expected a shape, found {value}"),
+            ));
+        }
+        let number = |key| get_field(value, path, key, "a number", JsonValue::as_f32);
+
+        Ok(Shape {
+            pos: vec2(number("x")?, number("y")?),
+            ty: match get_field(value, path, "shape", "a shape type", JsonValue::as_u8)? {
                 0 => ShapeType::Circle,
                 1 => ShapeType::Square,
                 2 => ShapeType::Triangle,
-                _ => panic!(),
+                other => {
+                    return Err(LoadError::new(
+                        format!("{path}.shape"),
+                        format!("unknown shape type {other}"),
+                    ))
+                }
+            },
+            size: number("scale")? - 1.0,
+            color: get_field(value, path, "color", "a color index 0–15", |color| {
+                color.as_usize().filter(|color| *color < 16)
+            })?,
+            moves: match &value["sequence"] {
+                JsonValue::Null => None,
+                sequence => Some(
+                    get_field(
+                        value,
+                        path,
+                        "sequence",
+                        "a string of moves",
+                        JsonValue::as_str,
+                    )?
+                    .chars()
+                    .map(|c| {
+                        Move::from_char(c).ok_or_else(|| {
+                            LoadError::new(
+                                format!("{path}.sequence"),
+                                format!("unknown move '{c}' in {sequence}"),
+                            )
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
+                ),
             },
-            size: value["scale"].as_f32().unwrap() - 1.0,
-            color: value["color"].as_usize().unwrap(),
             auto_shapes: value["auto_shapes"]
                 .members()
-                .map(Self::from_json)
+                .enumerate()
+                .map(|(i, shape)| Self::from_json(shape, &format!("{path}.auto_shapes[{i}]")))
+                .collect::<Result<_, _>>()?,
+            extra: value
+                .entries()
+                .filter(|(key, _)| !Self::KEYS.contains(key))
+                .map(|(key, value)| (key, value.clone()))
                 .collect(),
-            ..Default::default()
+            key_order: KeyOrder::of(value),
+        })
+    }
+
+    /// Writes shapes out for the clipboard, as one shape object or an array of them.
+    pub fn to_clipboard(shapes: &[&Shape]) -> String {
+        let json = match shapes {
+            [shape] => shape.as_json().into(),
+            shapes => JsonValue::Array(shapes.iter().map(|shape| shape.as_json().into()).collect()),
+        };
+        json::stringify_pretty(json, 4)
+    }
+
+    /// Reads shapes back from [`Shape::to_clipboard`]'s format.
+    pub fn from_clipboard(text: &str) -> Result<Vec<Shape>, LoadError> {
+        let json = json::parse(text)
+            .map_err(|_| LoadError::new("", "the clipboard doesn't hold shapes"))?;
+        if json.is_array() {
+            json.members()
+                .enumerate()
+                .map(|(i, shape)| Self::from_json(shape, &format!("[{i}]")))
+                .collect()
+        } else {
+            Ok(vec![Self::from_json(&json, "")?])
+        }
+    }
+
+    /// Performs `step`, or the fraction `amount` of it, keeping within the grid.
+    pub fn apply_move(&mut self, step: Move, amount: f32) {
+        match step {
+            Move::Up => self.pos.y = (self.pos.y - amount).max(0.0),
+            Move::Down => self.pos.y = (self.pos.y + amount).min(14.0),
+            Move::Left => self.pos.x = (self.pos.x - amount).max(0.0),
+            Move::Right => self.pos.x = (self.pos.x + amount).min(14.0),
+            Move::Expand => self.size = (self.size + amount).min(7.0),
+            Move::Shrink => self.size = (self.size - amount).max(0.0),
+            Move::Rest => {}
+        }
+    }
+
+    /// The square this shape covers in grid coordinates.
+    pub fn bounds(&self) -> Rect {
+        Rect::from_min_max(
+            Pos2::new(self.pos.x - self.size, self.pos.y - self.size),
+            Pos2::new(self.pos.x + self.size + 1.0, self.pos.y + self.size + 1.0),
+        )
+    }
+
+    pub fn contains(&self, point: Pos2) -> bool {
+        match self.ty {
+            ShapeType::Circle => point.distance(self.bounds().center()) <= self.size + 0.5,
+            ShapeType::Square => self.bounds().contains(point),
+            ShapeType::Triangle => {
+                let bounds = self.bounds();
+                let corners = [
+                    bounds.left_bottom(),
+                    bounds.right_bottom(),
+                    Pos2::new(bounds.center().x, bounds.top()),
+                ];
+                let sides = [0, 1, 2].map(|i| {
+                    let (from, to) = (corners[i], corners[(i + 1) % 3]);
+                    (to - from).x * (point - from).y - (to - from).y * (point - from).x
+                });
+                sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0)
+            }
         }
     }
 
     pub fn as_egui_shape(&self, transform: RectTransform, colors: &[[u8; 3]; 16]) -> egui::Shape {
-        let color_array = colors[self.color];
+        let color_array = colors.get(self.color).copied().unwrap_or_default();
         let color = Color32::from_rgb(color_array[0], color_array[1], color_array[2]);
 
         match self.ty {
@@ -91,6 +240,105 @@ impl Shape {
     }
 }
 
+#[cfg(test)]
+#[test]
+fn sequence_round_trip() {
+    let shape = Shape {
+        moves: Some(vec![
+            Move::Up,
+            Move::Rest,
+            Move::Expand,
+            Move::Left,
+            Move::Shrink,
+        ]),
+        ..Default::default()
+    };
+    let json = shape.as_json();
+    assert_eq!(json["sequence"], "U.ALB");
+
+    let loaded = Shape::from_json(&json.into(), "").unwrap();
+    assert_eq!(loaded.moves, shape.moves);
+    assert_eq!(
+        Shape::from_json(&Shape::default().as_json().into(), "")
+            .unwrap()
+            .moves,
+        None
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn auto_shapes_round_trip() {
+    let shape = Shape {
+        auto_shapes: vec![Shape {
+            ty: ShapeType::Square,
+            auto_shapes: vec![Shape {
+                ty: ShapeType::Triangle,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let loaded = Shape::from_json(&shape.as_json().into(), "").unwrap();
+    assert_eq!(loaded.auto_shapes.len(), 1);
+    assert_eq!(loaded.auto_shapes[0].ty, ShapeType::Square);
+    assert_eq!(loaded.auto_shapes[0].auto_shapes[0].ty, ShapeType::Triangle);
+}
+
+#[cfg(test)]
+#[test]
+fn clipboard_round_trip() {
+    let shapes = [
+        Shape {
+            moves: Some(vec![Move::Down, Move::Rest]),
+            auto_shapes: vec![Shape::default()],
+            ..Default::default()
+        },
+        Shape {
+            ty: ShapeType::Triangle,
+            pos: vec2(3.0, 4.0),
+            ..Default::default()
+        },
+    ];
+    let all = shapes.iter().collect::<Vec<_>>();
+    assert_eq!(
+        Shape::from_clipboard(&Shape::to_clipboard(&all)).unwrap(),
+        shapes
+    );
+    let one = Shape::to_clipboard(&all[1..]);
+    assert!(one.starts_with('{'));
+    assert_eq!(Shape::from_clipboard(&one).unwrap(), shapes[1..]);
+
+    assert!(Shape::from_clipboard("hello").is_err());
+    assert_eq!(
+        Shape::from_clipboard("[{}]").unwrap_err().to_string(),
+        "[0].x: missing, expected a number"
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn contains() {
+    let triangle = Shape {
+        ty: ShapeType::Triangle,
+        pos: vec2(5.0, 5.0),
+        size: 1.0,
+        ..Default::default()
+    };
+    assert!(triangle.contains(Pos2::new(5.5, 4.5)));
+    assert!(triangle.contains(Pos2::new(4.2, 6.8)));
+    assert!(!triangle.contains(Pos2::new(4.2, 4.2)));
+
+    let circle = Shape {
+        ty: ShapeType::Circle,
+        ..triangle
+    };
+    assert!(circle.contains(Pos2::new(4.5, 5.5)));
+    assert!(!circle.contains(Pos2::new(4.2, 4.2)));
+}
+
 impl Default for Shape {
     fn default() -> Self {
         Self {
@@ -100,18 +348,32 @@ impl Default for Shape {
             color: 0,
             moves: None,
             auto_shapes: vec![],
+            extra: Object::new(),
+            key_order: KeyOrder::default(),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ShapeType {
     Circle,
     Square,
     Triangle,
 }
 
-#[derive(Debug)]
+impl ShapeType {
+    pub const ALL: [Self; 3] = [Self::Circle, Self::Square, Self::Triangle];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Circle => "Circle",
+            Self::Square => "Square",
+            Self::Triangle => "Triangle",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Move {
     Up,
     Down,
@@ -119,4 +381,60 @@ pub enum Move {
     Right,
     Expand,
     Shrink,
+    Rest,
+}
+
+impl Move {
+    pub const ALL: [Self; 7] = [
+        Self::Up,
+        Self::Down,
+        Self::Left,
+        Self::Right,
+        Self::Expand,
+        Self::Shrink,
+        Self::Rest,
+    ];
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Up => "⬆",
+            Self::Down => "⬇",
+            Self::Left => "⬅",
+            Self::Right => "➡",
+            Self::Expand => "+",
+            Self::Shrink => "-",
+            Self::Rest => "·",
+        }
+    }
+
+    /// The character used for this move in a level's `sequence` string.
+    pub fn as_char(self) -> char {
+        match self {
+            Self::Up => 'U',
+            Self::Down => 'D',
+            Self::Left => 'L',
+            Self::Right => 'R',
+            Self::Expand => 'A',
+            Self::Shrink => 'B',
+            Self::Rest => '.',
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|step| step.as_char() == c.to_ascii_uppercase())
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Up => "Up",
+            Self::Down => "Down",
+            Self::Left => "Left",
+            Self::Right => "Right",
+            Self::Expand => "Expand",
+            Self::Shrink => "Shrink",
+            Self::Rest => "Rest",
+        }
+    }
 }
@@ -0,0 +1,204 @@
+use std::time::{Duration, Instant};
+
+use crate::{audio::Song, project::Header};
+
+/// Slowest and fastest tempos [`analyse`] will consider.
+const BPM_RANGE: (f32, f32) = (60.0, 200.0);
+/// Onset envelope frames per second.
+const FRAME_RATE: u32 = 200;
+
+/// Tempo and beat grid estimated from a song.
+#[derive(Clone, Copy, Debug)]
+pub struct Estimate {
+    pub bpm: f32,
+    /// Time of the first beat in seconds.
+    pub first_beat: f32,
+}
+
+impl Estimate {
+    /// Offset in beats at the estimated tempo before the first beat, for [`Header::offset`].
+    pub fn offset(&self, header: &Header) -> u16 {
+        let beat_length =
+            60.0 / self.bpm.round() * 4.0 / f32::from(header.time_signature_bottom.get());
+        ((self.first_beat / beat_length).round() as u16).max(1)
+    }
+}
+
+/// Estimates the tempo of `song` from the autocorrelation of its onsets, and
+/// where the first beat falls. Bars aren't detected, so this may not be a
+/// downbeat.
+pub fn analyse(song: &Song) -> Option<Estimate> {
+    let onsets = onset_envelope(song);
+    let period = beat_period(&onsets)?;
+
+    // Line a grid with this period up with the onsets, then find the first
+    // strong onset on it
+    let phases = 0..period.ceil() as usize;
+    let grid = |phase: usize| {
+        (0..)
+            .map(move |beat| (phase as f32 + beat as f32 * period).round() as usize)
+            .take_while(|frame| *frame < onsets.len())
+    };
+    let phase = phases.max_by(|a, b| {
+        let score = |phase| grid(phase).map(|frame| onsets[frame]).sum::<f32>();
+        score(*a).total_cmp(&score(*b))
+    })?;
+    let threshold = onsets.iter().copied().fold(0.0, f32::max) * 0.3;
+    let first_beat = grid(phase).find(|frame| {
+        onsets[frame.saturating_sub(1)..(frame + 2).min(onsets.len())]
+            .iter()
+            .any(|onset| *onset >= threshold)
+    })?;
+
+    Some(Estimate {
+        bpm: 60.0 * FRAME_RATE as f32 / period,
+        first_beat: first_beat as f32 / FRAME_RATE as f32,
+    })
+}
+
+/// How sharply the loudness rises in each frame.
+fn onset_envelope(song: &Song) -> Vec<f32> {
+    let hop = (song.sample_rate / FRAME_RATE) as usize * usize::from(song.channels);
+    let loudness = song
+        .samples
+        .chunks(hop.max(1))
+        .map(|frame| {
+            let energy = frame.iter().map(|sample| sample * sample).sum::<f32>();
+            (1.0 + 1000.0 * energy / frame.len() as f32).ln()
+        })
+        .collect::<Vec<_>>();
+    let mut onsets = vec![0.0];
+    onsets.extend(loudness.windows(2).map(|pair| (pair[1] - pair[0]).max(0.0)));
+    onsets
+}
+
+/// Finds the beat length in frames with the strongest autocorrelation,
+/// preferring faster tempos when multiples score about as well.
+fn beat_period(onsets: &[f32]) -> Option<f32> {
+    let min_lag = (60.0 * FRAME_RATE as f32 / BPM_RANGE.1).floor() as usize;
+    let max_lag = (60.0 * FRAME_RATE as f32 / BPM_RANGE.0).ceil() as usize;
+    if onsets.len() <= max_lag + 1 {
+        return None;
+    }
+    let correlation = (0..=max_lag + 1)
+        .map(|lag| {
+            let sum = onsets
+                .iter()
+                .zip(&onsets[lag..])
+                .map(|(a, b)| a * b)
+                .sum::<f32>();
+            sum / (onsets.len() - lag) as f32
+        })
+        .collect::<Vec<_>>();
+
+    let best = correlation[min_lag..=max_lag]
+        .iter()
+        .copied()
+        .fold(0.0, f32::max);
+    if best <= 0.0 {
+        return None;
+    }
+    let lag = (min_lag..=max_lag).find(|lag| {
+        let value = correlation[*lag];
+        value >= best * 0.9 && value >= correlation[lag - 1] && value >= correlation[lag + 1]
+    })?;
+
+    // Fit a parabola through the peak for a fractional lag
+    let (before, peak, after) = (correlation[lag - 1], correlation[lag], correlation[lag + 1]);
+    let curvature = before - 2.0 * peak + after;
+    let shift = if curvature < 0.0 {
+        (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    Some(lag as f32 + shift)
+}
+
+/// Estimates a tempo from the user tapping along.
+#[derive(Default)]
+pub struct TapTempo {
+    taps: Vec<Instant>,
+}
+
+impl TapTempo {
+    /// Taps further apart than this start a new measurement.
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    pub fn tap(&mut self) {
+        self.tap_at(Instant::now());
+    }
+
+    fn tap_at(&mut self, now: Instant) {
+        if self
+            .taps
+            .last()
+            .is_some_and(|last| now.duration_since(*last) > Self::TIMEOUT)
+        {
+            self.taps.clear();
+        }
+        self.taps.push(now);
+    }
+
+    /// The tempo of the taps so far, once there are enough to tell.
+    pub fn bpm(&self) -> Option<f32> {
+        let (first, last) = (self.taps.first()?, self.taps.last()?);
+        if self.taps.len() < 4 {
+            return None;
+        }
+        let interval = last.duration_since(*first).as_secs_f32() / (self.taps.len() - 1) as f32;
+        Some(60.0 / interval)
+    }
+}
+
+#[cfg(test)]
+fn click_track(bpm: f32, first_beat: f32, seconds: f32) -> Song {
+    let sample_rate = 8000;
+    let mut samples = vec![0.0; (seconds * sample_rate as f32) as usize];
+    let mut beat = first_beat;
+    while beat < seconds {
+        let start = (beat * sample_rate as f32) as usize;
+        for (i, sample) in samples.iter_mut().skip(start).take(80).enumerate() {
+            let time = i as f32 / sample_rate as f32;
+            *sample = (time * 1000.0 * std::f32::consts::TAU).sin() * (1.0 - i as f32 / 80.0);
+        }
+        beat += 60.0 / bpm;
+    }
+    Song {
+        path: "click.wav".into(),
+        sample_rate,
+        channels: 1,
+        samples: samples.into(),
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn detects_click_tracks() {
+    for (bpm, first_beat) in [(100.0, 1.0), (140.0, 0.25), (87.0, 2.3)] {
+        let estimate = analyse(&click_track(bpm, first_beat, 20.0)).unwrap();
+        assert!(
+            (estimate.bpm - bpm).abs() < 1.0,
+            "expected {bpm} BPM, found {}",
+            estimate.bpm
+        );
+        assert!(
+            (estimate.first_beat - first_beat).abs() < 0.03,
+            "expected first beat at {first_beat}, found {}",
+            estimate.first_beat
+        );
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn tap_tempo() {
+    let start = Instant::now();
+    let mut taps = TapTempo::default();
+    for i in 0..4 {
+        taps.tap_at(start + Duration::from_millis(500 * i));
+    }
+    assert!((taps.bpm().unwrap() - 120.0).abs() < 0.01);
+
+    taps.tap_at(start + Duration::from_secs(10));
+    assert_eq!(taps.bpm(), None);
+}
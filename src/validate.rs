@@ -0,0 +1,171 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::{project::Project, shape::Shape};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    /// The game can't play the level as it is.
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Index of the shape at fault, if it's not the level as a whole.
+    pub shape: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning")?,
+            Severity::Error => write!(f, "error")?,
+        }
+        if let Some(shape) = self.shape {
+            write!(f, " in shape {}", shape + 1)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Checks that a project is something Open Manifold can play.
+pub fn validate(project: &Project) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut report = |severity, shape, message: String| {
+        diagnostics.push(Diagnostic {
+            severity,
+            shape,
+            message,
+        });
+    };
+
+    let header = &project.header;
+    if header.name.trim().is_empty() {
+        report(Severity::Error, None, "The level has no name".to_owned());
+    }
+    for (field, value) in [
+        ("genre", &header.genre),
+        ("level author", &header.level_author),
+        ("song author", &header.song_author),
+    ] {
+        if value.trim().is_empty() {
+            report(Severity::Warning, None, format!("The {field} is empty"));
+        }
+    }
+    if usize::from(header.bg_color) >= header.color_table.len() {
+        report(
+            Severity::Error,
+            None,
+            format!(
+                "Background color {} isn't in the color table",
+                header.bg_color
+            ),
+        );
+    }
+    if project.shapes.is_empty() {
+        report(Severity::Error, None, "The level has no shapes".to_owned());
+    }
+
+    for (i, shape) in project.shapes.iter().enumerate() {
+        let mut check = |shape: &Shape, name: &str| {
+            if shape.color >= header.color_table.len() {
+                report(
+                    Severity::Error,
+                    Some(i),
+                    format!("{name} color {} isn't in the color table", shape.color),
+                );
+            }
+            if !(0.0..=14.0).contains(&shape.pos.x) || !(0.0..=14.0).contains(&shape.pos.y) {
+                report(
+                    Severity::Error,
+                    Some(i),
+                    format!(
+                        "{name} position ({}, {}) is outside the 15×15 grid",
+                        shape.pos.x, shape.pos.y
+                    ),
+                );
+            } else if shape.pos != shape.pos.round() {
+                report(
+                    Severity::Warning,
+                    Some(i),
+                    format!("{name} position isn't on the grid"),
+                );
+            }
+            if !(0.0..=7.0).contains(&shape.size) {
+                report(
+                    Severity::Error,
+                    Some(i),
+                    format!(
+                        "{name} scale {} is outside the game's range of 1 to 8",
+                        shape.size + 1.0
+                    ),
+                );
+            }
+        };
+
+        check(shape, "Shape");
+        for (j, auto_shape) in shape.auto_shapes.iter().enumerate() {
+            check(auto_shape, &format!("Auto shape {}", j + 1));
+        }
+        if shape.moves.as_ref().is_some_and(Vec::is_empty) {
+            report(
+                Severity::Warning,
+                Some(i),
+                "The sequence is empty".to_owned(),
+            );
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+#[test]
+fn finds_problems() {
+    use egui::vec2;
+
+    assert_eq!(
+        validate(&Project::default()),
+        [Diagnostic {
+            severity: Severity::Error,
+            shape: None,
+            message: "The level has no shapes".to_owned(),
+        }]
+    );
+
+    let project = Project {
+        shapes: vec![
+            Shape::default(),
+            Shape {
+                color: 16,
+                pos: vec2(15.0, 3.0),
+                ..Default::default()
+            },
+            Shape {
+                moves: Some(vec![]),
+                auto_shapes: vec![Shape {
+                    size: 9.0,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+    let diagnostics = validate(&project);
+    let problems = diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.severity, diagnostic.shape))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        problems,
+        [
+            (Severity::Error, Some(1)),
+            (Severity::Error, Some(1)),
+            (Severity::Error, Some(2)),
+            (Severity::Warning, Some(2)),
+        ]
+    );
+}